@@ -5,8 +5,7 @@ use std::fmt;
 use rmpv::Value;
 use skulpin::skia_safe::Color4f;
 
-use crate::editor::EDITOR;
-use crate::editor::{Colors, CursorMode, CursorShape, Style};
+use crate::editor::{Colors, CursorMode, CursorShape, Editor, Style, UnderlineStyle, EDITOR};
 use crate::error_handling::ResultPanicExplanation;
 
 #[derive(Debug, Clone)]
@@ -99,7 +98,8 @@ pub enum GuiOption {
     GuiFont(String),
     GuiFontSet(String),
     GuiFontWide(String),
-    LineSpace(u64),
+    LineSpace(i64),
+    MouseMoveEvent(bool),
     Pumblend(u64),
     ShowTabLine(u64),
     TermGuiColors(bool),
@@ -114,7 +114,7 @@ pub enum WindowAnchor {
     SouthEast,
 }
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq)]
 pub enum EditorMode {
     // The set of modes reported will change in new versions of Nvim, for
     // instance more sub-modes and temporary states might be represented as
@@ -147,6 +147,7 @@ pub enum RedrawEvent {
     MouseOff,
     BusyStart,
     BusyStop,
+    Suspend,
     Flush,
     Resize {
         grid: u64,
@@ -160,6 +161,10 @@ pub enum RedrawEvent {
         id: u64,
         style: Style,
     },
+    HighlightGroupSet {
+        name: String,
+        id: u64,
+    },
     GridLine {
         grid: u64,
         row: u64,
@@ -210,6 +215,14 @@ pub enum RedrawEvent {
     WindowClose {
         grid: u64,
     },
+    WindowViewport {
+        grid: u64,
+        top_line: u64,
+        bottom_line: u64,
+        current_line: u64,
+        current_column: u64,
+        scroll_delta: i64,
+    },
     MessageSetPosition {
         grid: u64,
         row: u64,
@@ -241,6 +254,13 @@ pub enum RedrawEvent {
         line: StyledContent,
     },
     CommandLineBlockHide,
+    WildmenuShow {
+        items: Vec<String>,
+    },
+    WildmenuSelect {
+        selected: i64,
+    },
+    WildmenuHide,
     MessageShow {
         kind: MessageKind,
         content: StyledContent,
@@ -274,6 +294,15 @@ fn unpack_color(packed_color: u64) -> Color4f {
     }
 }
 
+// Neovim sends -1 for colors which aren't set
+fn decode_color(packed_color: i64) -> Option<Color4f> {
+    if (0..=0xff_ffff).contains(&packed_color) {
+        Some(unpack_color(packed_color as u64))
+    } else {
+        None
+    }
+}
+
 fn extract_values<Arr: AsMut<[Value]>>(values: Vec<Value>, mut arr: Arr) -> Result<Arr> {
     let arr_ref = arr.as_mut();
 
@@ -337,6 +366,9 @@ fn parse_mode_info_set(mode_info_set_arguments: Vec<Value>) -> Result<RedrawEven
 
         for (name, value) in info_map {
             match parse_string(name)?.as_str() {
+                "name" => {
+                    mode_info.name = Some(parse_string(value)?);
+                }
                 "cursor_shape" => {
                     mode_info.shape = CursorShape::from_type_name(&parse_string(value)?);
                 }
@@ -378,7 +410,8 @@ fn parse_option_set(option_set_arguments: Vec<Value>) -> Result<RedrawEvent> {
             "guifont" => GuiOption::GuiFont(parse_string(value)?),
             "guifontset" => GuiOption::GuiFontSet(parse_string(value)?),
             "guifontwide" => GuiOption::GuiFontWide(parse_string(value)?),
-            "linespace" => GuiOption::LineSpace(parse_u64(value)?),
+            "linespace" => GuiOption::LineSpace(parse_i64(value)?),
+            "mousemoveevent" => GuiOption::MouseMoveEvent(parse_bool(value)?),
             "pumblend" => GuiOption::Pumblend(parse_u64(value)?),
             "showtabline" => GuiOption::ShowTabLine(parse_u64(value)?),
             "termguicolors" => GuiOption::TermGuiColors(parse_bool(value)?),
@@ -403,6 +436,32 @@ fn parse_mode_change(mode_change_arguments: Vec<Value>) -> Result<RedrawEvent> {
     })
 }
 
+// Parses the map returned by nvim_get_mode into the mode and whether Neovim is blocked
+// waiting for input. Modes are short codes such as "n", "no" or "ic"
+pub fn parse_mode_status(mode_status: Vec<(Value, Value)>) -> Result<(EditorMode, bool)> {
+    let mut mode = EditorMode::Unknown(String::from(""));
+    let mut blocking = false;
+
+    for (name, value) in mode_status {
+        match parse_string(name)?.as_str() {
+            "mode" => {
+                let mode_name = parse_string(value)?;
+                mode = match mode_name.as_str() {
+                    "n" | "niI" | "niR" | "niV" | "nt" => EditorMode::Normal,
+                    "i" | "ic" | "ix" => EditorMode::Insert,
+                    "v" | "vs" | "V" | "Vs" | "\u{16}" | "\u{16}s" => EditorMode::Visual,
+                    "c" | "cv" | "ce" => EditorMode::CmdLine,
+                    _ => EditorMode::Unknown(mode_name),
+                };
+            }
+            "blocking" => blocking = parse_bool(value)?,
+            _ => {}
+        }
+    }
+
+    Ok((mode, blocking))
+}
+
 fn parse_grid_resize(grid_resize_arguments: Vec<Value>) -> Result<RedrawEvent> {
     let [grid_id, width, height] =
         extract_values(grid_resize_arguments, [Value::Nil, Value::Nil, Value::Nil])?;
@@ -428,6 +487,14 @@ fn parse_default_colors(default_colors_arguments: Vec<Value>) -> Result<RedrawEv
     })
 }
 
+fn set_underline_style(style: &mut Style, underline_style: UnderlineStyle, enabled: bool) {
+    if enabled {
+        style.underline_style = underline_style;
+    } else if style.underline_style == underline_style {
+        style.underline_style = UnderlineStyle::None;
+    }
+}
+
 fn parse_style(style_map: Value) -> Result<Style> {
     let attributes = parse_map(style_map)?;
 
@@ -437,13 +504,13 @@ fn parse_style(style_map: Value) -> Result<Style> {
         if let (Value::String(name), value) = attribute {
             match (name.as_str().unwrap(), value) {
                 ("foreground", Value::Integer(packed_color)) => {
-                    style.colors.foreground = Some(unpack_color(packed_color.as_u64().unwrap()))
+                    style.colors.foreground = packed_color.as_i64().and_then(decode_color)
                 }
                 ("background", Value::Integer(packed_color)) => {
-                    style.colors.background = Some(unpack_color(packed_color.as_u64().unwrap()))
+                    style.colors.background = packed_color.as_i64().and_then(decode_color)
                 }
                 ("special", Value::Integer(packed_color)) => {
-                    style.colors.special = Some(unpack_color(packed_color.as_u64().unwrap()))
+                    style.colors.special = packed_color.as_i64().and_then(decode_color)
                 }
                 ("reverse", Value::Boolean(reverse)) => style.reverse = reverse,
                 ("italic", Value::Boolean(italic)) => style.italic = italic,
@@ -451,9 +518,28 @@ fn parse_style(style_map: Value) -> Result<Style> {
                 ("strikethrough", Value::Boolean(strikethrough)) => {
                     style.strikethrough = strikethrough
                 }
-                ("underline", Value::Boolean(underline)) => style.underline = underline,
-                ("undercurl", Value::Boolean(undercurl)) => style.undercurl = undercurl,
+                ("underline", Value::Boolean(underline)) => {
+                    style.underline = underline;
+                    set_underline_style(&mut style, UnderlineStyle::Single, underline);
+                }
+                ("undercurl", Value::Boolean(undercurl)) => {
+                    style.undercurl = undercurl;
+                    set_underline_style(&mut style, UnderlineStyle::Curl, undercurl);
+                }
+                ("underdouble", Value::Boolean(underdouble)) => {
+                    style.underline |= underdouble;
+                    set_underline_style(&mut style, UnderlineStyle::Double, underdouble);
+                }
+                ("underdotted", Value::Boolean(underdotted)) => {
+                    style.underline |= underdotted;
+                    set_underline_style(&mut style, UnderlineStyle::Dotted, underdotted);
+                }
+                ("underdashed", Value::Boolean(underdashed)) => {
+                    style.underline |= underdashed;
+                    set_underline_style(&mut style, UnderlineStyle::Dashed, underdashed);
+                }
                 ("blend", Value::Integer(blend)) => style.blend = blend.as_u64().unwrap() as u8,
+                ("url", Value::String(url)) => style.url = url.into_str(),
                 _ => println!("Ignored style attribute: {}", name),
             }
         } else {
@@ -476,6 +562,15 @@ fn parse_hl_attr_define(hl_attr_define_arguments: Vec<Value>) -> Result<RedrawEv
     })
 }
 
+fn parse_hl_group_set(hl_group_set_arguments: Vec<Value>) -> Result<RedrawEvent> {
+    let [name, id] = extract_values(hl_group_set_arguments, [Value::Nil, Value::Nil])?;
+
+    Ok(RedrawEvent::HighlightGroupSet {
+        name: parse_string(name)?,
+        id: parse_u64(id)?,
+    })
+}
+
 fn parse_grid_line_cell(grid_line_cell: Value) -> Result<GridLineCell> {
     fn take_value(val: &mut Value) -> Value {
         std::mem::replace(val, Value::Nil)
@@ -488,6 +583,8 @@ fn parse_grid_line_cell(grid_line_cell: Value) -> Result<GridLineCell> {
         .map(|v| take_value(v))
         .ok_or(EventParseError::InvalidEventFormat)?;
 
+    // An omitted id means the previous cell's highlight is reused, while an explicit 0 always
+    // means the default highlight, so the two must stay distinct
     let highlight_id = cell_contents
         .get_mut(1)
         .map(|v| take_value(v))
@@ -645,6 +742,44 @@ fn parse_win_close(win_close_arguments: Vec<Value>) -> Result<RedrawEvent> {
     })
 }
 
+fn parse_win_viewport(mut win_viewport_arguments: Vec<Value>) -> Result<RedrawEvent> {
+    // Newer versions of Neovim append the line count, then the scroll delta. Anything past
+    // those is from a version newer still and is ignored
+    win_viewport_arguments.truncate(8);
+    let scroll_delta = match win_viewport_arguments.len() {
+        8 => {
+            let scroll_delta = win_viewport_arguments.pop().unwrap();
+            win_viewport_arguments.pop();
+            parse_i64(scroll_delta)?
+        }
+        7 => {
+            win_viewport_arguments.pop();
+            0
+        }
+        _ => 0,
+    };
+
+    let values = [
+        Value::Nil,
+        Value::Nil,
+        Value::Nil,
+        Value::Nil,
+        Value::Nil,
+        Value::Nil,
+    ];
+    let [grid, _window, top_line, bottom_line, current_line, current_column] =
+        extract_values(win_viewport_arguments, values)?;
+
+    Ok(RedrawEvent::WindowViewport {
+        grid: parse_u64(grid)?,
+        top_line: parse_u64(top_line)?,
+        bottom_line: parse_u64(bottom_line)?,
+        current_line: parse_u64(current_line)?,
+        current_column: parse_u64(current_column)?,
+        scroll_delta,
+    })
+}
+
 fn parse_msg_set_pos(msg_set_pos_arguments: Vec<Value>) -> Result<RedrawEvent> {
     let values = [Value::Nil, Value::Nil, Value::Nil, Value::Nil];
     let [grid, row, scrolled, separator_character] = extract_values(msg_set_pos_arguments, values)?;
@@ -731,6 +866,25 @@ fn parse_cmdline_block_append(cmdline_block_append_arguments: Vec<Value>) -> Res
     })
 }
 
+fn parse_wildmenu_show(wildmenu_show_arguments: Vec<Value>) -> Result<RedrawEvent> {
+    let [items] = extract_values(wildmenu_show_arguments, [Value::Nil])?;
+
+    Ok(RedrawEvent::WildmenuShow {
+        items: parse_array(items)?
+            .into_iter()
+            .map(parse_string)
+            .collect::<Result<_>>()?,
+    })
+}
+
+fn parse_wildmenu_select(wildmenu_select_arguments: Vec<Value>) -> Result<RedrawEvent> {
+    let [selected] = extract_values(wildmenu_select_arguments, [Value::Nil])?;
+
+    Ok(RedrawEvent::WildmenuSelect {
+        selected: parse_i64(selected)?,
+    })
+}
+
 fn parse_msg_show(msg_show_arguments: Vec<Value>) -> Result<RedrawEvent> {
     let [kind, content, replace_last] =
         extract_values(msg_show_arguments, [Value::Nil, Value::Nil, Value::Nil])?;
@@ -808,10 +962,12 @@ pub fn parse_redraw_event(event_value: Value) -> Result<Vec<RedrawEvent>> {
             "mouse_off" => Some(RedrawEvent::MouseOff),
             "busy_start" => Some(RedrawEvent::BusyStart),
             "busy_stop" => Some(RedrawEvent::BusyStop),
+            "suspend" => Some(RedrawEvent::Suspend),
             "flush" => Some(RedrawEvent::Flush),
             "grid_resize" => Some(parse_grid_resize(event_parameters)?),
             "default_colors_set" => Some(parse_default_colors(event_parameters)?),
             "hl_attr_define" => Some(parse_hl_attr_define(event_parameters)?),
+            "hl_group_set" => Some(parse_hl_group_set(event_parameters)?),
             "grid_line" => Some(parse_grid_line(event_parameters)?),
             "grid_clear" => Some(parse_clear(event_parameters)?),
             "grid_cursor_goto" => Some(parse_cursor_goto(event_parameters)?),
@@ -821,6 +977,7 @@ pub fn parse_redraw_event(event_value: Value) -> Result<Vec<RedrawEvent>> {
             "win_external_pos" => Some(parse_win_external_pos(event_parameters)?),
             "win_hide" => Some(parse_win_hide(event_parameters)?),
             "win_close" => Some(parse_win_close(event_parameters)?),
+            "win_viewport" => Some(parse_win_viewport(event_parameters)?),
             "msg_set_pos" => Some(parse_msg_set_pos(event_parameters)?),
             "cmdline_show" => Some(parse_cmdline_show(event_parameters)?),
             "cmdline_pos" => Some(parse_cmdline_pos(event_parameters)?),
@@ -829,6 +986,9 @@ pub fn parse_redraw_event(event_value: Value) -> Result<Vec<RedrawEvent>> {
             "cmdline_block_show" => Some(parse_cmdline_block_show(event_parameters)?),
             "cmdline_block_append" => Some(parse_cmdline_block_append(event_parameters)?),
             "cmdline_block_hide" => Some(RedrawEvent::CommandLineBlockHide),
+            "wildmenu_show" => Some(parse_wildmenu_show(event_parameters)?),
+            "wildmenu_select" => Some(parse_wildmenu_select(event_parameters)?),
+            "wildmenu_hide" => Some(RedrawEvent::WildmenuHide),
             "msg_show" => Some(parse_msg_show(event_parameters)?),
             "msg_clear" => Some(RedrawEvent::MessageClear),
             "msg_showmode" => Some(parse_msg_showmode(event_parameters)?),
@@ -851,9 +1011,208 @@ pub(super) fn handle_redraw_event_group(arguments: Vec<Value>) {
         let parsed_events = parse_redraw_event(events)
             .unwrap_or_explained_panic("Could not parse event from neovim");
 
+        let mut editor = EDITOR.lock();
+        let mut grid_lines = Vec::new();
+
+        for parsed_event in parsed_events {
+            if let RedrawEvent::GridLine {
+                grid,
+                row,
+                column_start,
+                cells,
+            } = parsed_event
+            {
+                grid_lines.push((grid, row, column_start, cells));
+            } else {
+                editor.handle_redraw_event(parsed_event);
+            }
+        }
+
+        if !grid_lines.is_empty() {
+            editor.apply_grid_lines(grid_lines);
+        }
+    }
+}
+
+// Replays a captured sequence of (event name, arguments) pairs against an editor so
+// rendering bugs can be reproduced without a running Neovim
+#[allow(dead_code)]
+pub fn apply_event_log(editor: &mut Editor, log: &[(String, Vec<Value>)]) {
+    for (event_name, arguments) in log {
+        let event = Value::from(vec![
+            Value::from(event_name.as_str()),
+            Value::from(arguments.clone()),
+        ]);
+        let parsed_events = parse_redraw_event(event)
+            .unwrap_or_explained_panic("Could not parse event from the event log");
+
         for parsed_event in parsed_events {
-            let mut editor = EDITOR.lock();
             editor.handle_redraw_event(parsed_event);
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_grid_line_cell_highlight_id() {
+        let explicit_default = Value::from(vec![Value::from("a"), Value::from(0)]);
+        let omitted = Value::from(vec![Value::from("b")]);
+
+        // RUN FUNCTION
+        assert_eq!(
+            parse_grid_line_cell(explicit_default).unwrap().highlight_id,
+            Some(0)
+        );
+        assert_eq!(parse_grid_line_cell(omitted).unwrap().highlight_id, None);
+    }
+
+    #[test]
+    fn test_decode_color() {
+        // RUN FUNCTION
+        assert_eq!(
+            decode_color(0x000000),
+            Some(Color4f::new(0.0, 0.0, 0.0, 1.0))
+        );
+        assert_eq!(
+            decode_color(0xffffff),
+            Some(Color4f::new(1.0, 1.0, 1.0, 1.0))
+        );
+        assert_eq!(
+            decode_color(0x336699),
+            Some(Color4f::new(0.2, 0.4, 0.6, 1.0))
+        );
+        assert_eq!(decode_color(-1), None);
+        assert_eq!(decode_color(0x1_000_000), None);
+    }
+
+    #[test]
+    fn test_parse_style_underline_styles() {
+        let parse_underline = |attribute: &str| {
+            let attributes = Value::Map(vec![(Value::from(attribute), Value::from(true))]);
+            parse_style(attributes).unwrap()
+        };
+
+        // RUN FUNCTION
+        assert_eq!(
+            parse_underline("underline").underline_style,
+            UnderlineStyle::Single
+        );
+        assert_eq!(
+            parse_underline("undercurl").underline_style,
+            UnderlineStyle::Curl
+        );
+        assert_eq!(
+            parse_underline("underdouble").underline_style,
+            UnderlineStyle::Double
+        );
+        assert_eq!(
+            parse_underline("underdotted").underline_style,
+            UnderlineStyle::Dotted
+        );
+        assert_eq!(
+            parse_underline("underdashed").underline_style,
+            UnderlineStyle::Dashed
+        );
+        assert!(parse_underline("underdashed").underline);
+        assert_eq!(
+            parse_underline("bold").underline_style,
+            UnderlineStyle::None
+        );
+    }
+
+    #[test]
+    fn test_parse_win_viewport_lengths() {
+        let arguments = |count: usize| -> Vec<Value> {
+            vec![2, 1000, 10, 40, 12, 3, 500, -4, 99]
+                .into_iter()
+                .take(count)
+                .map(Value::from)
+                .collect()
+        };
+        let scroll_delta = |count| match parse_win_viewport(arguments(count)).unwrap() {
+            RedrawEvent::WindowViewport {
+                grid,
+                top_line,
+                bottom_line,
+                current_line,
+                current_column,
+                scroll_delta,
+            } => {
+                assert_eq!(
+                    (grid, top_line, bottom_line, current_line, current_column),
+                    (2, 10, 40, 12, 3)
+                );
+                scroll_delta
+            }
+            _ => panic!("Expected a window viewport"),
+        };
+
+        // RUN FUNCTION
+        assert_eq!(scroll_delta(6), 0);
+        assert_eq!(scroll_delta(7), 0);
+        assert_eq!(scroll_delta(8), -4);
+        assert_eq!(scroll_delta(9), -4);
+        assert!(parse_win_viewport(arguments(5)).is_err());
+    }
+
+    #[test]
+    fn test_parse_style_url() {
+        let attributes = Value::Map(vec![
+            (Value::from("url"), Value::from("https://neovim.io")),
+            (Value::from("underline"), Value::from(true)),
+        ]);
+
+        // RUN FUNCTION
+        let style = parse_style(attributes).unwrap();
+        assert_eq!(style.url, Some("https://neovim.io".to_string()));
+        assert!(style.underline);
+
+        let plain = parse_style(Value::Map(Vec::new())).unwrap();
+        assert_eq!(plain.url, None);
+    }
+
+    #[test]
+    fn test_apply_event_log() {
+        crate::redraw_scheduler::initialize_settings();
+        let mut editor = Editor::new_headless(100, 50);
+        let attributes = Value::Map(vec![(Value::from("bold"), Value::from(true))]);
+        let cells = Value::from(vec![
+            Value::from(vec![Value::from("a"), Value::from(1)]),
+            Value::from(vec![Value::from("b"), Value::from(0), Value::from(2)]),
+        ]);
+        let log = vec![
+            (
+                "grid_resize".to_string(),
+                vec![Value::from(1), Value::from(4), Value::from(2)],
+            ),
+            (
+                "hl_attr_define".to_string(),
+                vec![
+                    Value::from(1),
+                    attributes,
+                    Value::Map(vec![]),
+                    Value::from(Vec::<Value>::new()),
+                ],
+            ),
+            (
+                "grid_line".to_string(),
+                vec![Value::from(1), Value::from(1), Value::from(0), cells],
+            ),
+            ("flush".to_string(), vec![]),
+        ];
+
+        // RUN FUNCTION
+        apply_event_log(&mut editor, &log);
+
+        assert_eq!((editor.grid.width, editor.grid.height), (4, 2));
+        let (text, style) = editor.grid.get_cell(0, 1).unwrap().clone().unwrap();
+        assert_eq!(text, "a");
+        assert!(style.unwrap().bold);
+        assert_eq!(editor.grid.get_cell(1, 1).unwrap().clone().unwrap().0, "b");
+        assert_eq!(editor.grid.get_cell(2, 1).unwrap().clone().unwrap().0, "b");
+        assert_eq!(editor.grid.get_cell(3, 1), Some(&None));
+    }
+}
@@ -6,6 +6,7 @@ use tokio::process::ChildStdin;
 use tokio::task;
 
 use super::events::handle_redraw_event_group;
+use crate::editor::EDITOR;
 use crate::settings::SETTINGS;
 
 #[derive(Clone)]
@@ -29,6 +30,13 @@ impl Handler for NeovimHandler {
             "setting_changed" => {
                 SETTINGS.handle_changed_notification(arguments);
             }
+            "dir_changed" => {
+                if let Some(Value::String(dir)) = arguments.into_iter().next() {
+                    if let Some(dir) = dir.into_str() {
+                        EDITOR.lock().set_working_dir(dir);
+                    }
+                }
+            }
             _ => {}
         })
         .await
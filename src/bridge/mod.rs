@@ -10,15 +10,15 @@ use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 
 use log::{error, info, trace};
-use nvim_rs::{create::tokio as create, UiAttachOptions};
+use nvim_rs::create::tokio as create;
 use rmpv::Value;
 use tokio::process::Command;
 use tokio::runtime::Runtime;
 use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender};
 
+use crate::editor::{AttachOptions, EDITOR};
 use crate::error_handling::ResultPanicExplanation;
 use crate::settings::*;
-use crate::window::window_geometry_or_default;
 pub use events::*;
 use handler::NeovimHandler;
 pub use layouts::*;
@@ -101,7 +101,6 @@ async fn drain(receiver: &mut UnboundedReceiver<UiCommand>) -> Option<Vec<UiComm
 }
 
 async fn start_process(mut receiver: UnboundedReceiver<UiCommand>) {
-    let (width, height) = window_geometry_or_default();
     let (mut nvim, io_handler, _) =
         create::new_child_cmd(&mut create_nvim_command(), NeovimHandler())
             .await
@@ -134,9 +133,6 @@ async fn start_process(mut receiver: UnboundedReceiver<UiCommand>) {
     nvim.set_var("neovide", Value::Boolean(true))
         .await
         .unwrap_or_explained_panic("Could not communicate with neovim process");
-    let mut options = UiAttachOptions::new();
-    options.set_linegrid_external(true);
-    options.set_rgb(true);
     if let Err(command_error) = nvim.command("runtime! ginit.vim").await {
         nvim.command(&format!(
             "echomsg \"error encountered in ginit.vim {:?}\"",
@@ -145,9 +141,11 @@ async fn start_process(mut receiver: UnboundedReceiver<UiCommand>) {
         .await
         .ok();
     }
-    nvim.ui_attach(width as i64, height as i64, &options)
+    let attach_arguments = EDITOR.lock().attach(AttachOptions::default());
+    nvim.call("nvim_ui_attach", attach_arguments)
         .await
-        .unwrap_or_explained_panic("Could not attach ui to neovim process");
+        .unwrap_or_explained_panic("Could not attach ui to neovim process")
+        .unwrap_or_explained_panic("Neovim rejected the ui attach");
     info!("Neovim process attached");
 
     let nvim = Arc::new(nvim);
@@ -183,6 +181,22 @@ async fn start_process(mut receiver: UnboundedReceiver<UiCommand>) {
     SETTINGS.read_initial_values(&nvim).await;
     SETTINGS.setup_changed_listeners(&nvim).await;
 
+    // The group is cleared first so attaching again doesn't register a second notification.
+    // Each line is its own command since a `|` would otherwise end up inside the autocmd
+    for command in &[
+        "augroup neovide_dir",
+        "autocmd!",
+        "autocmd DirChanged * call rpcnotify(1, 'dir_changed', getcwd())",
+        "augroup END",
+    ] {
+        nvim.command(command).await.ok();
+    }
+    if let Ok(Value::String(dir)) = nvim.call_function("getcwd", vec![]).await {
+        if let Some(dir) = dir.into_str() {
+            EDITOR.lock().set_working_dir(dir);
+        }
+    }
+
     nvim.set_option("lazyredraw", Value::Boolean(false))
         .await
         .ok();
@@ -15,15 +15,22 @@ pub enum UiCommand {
     MouseButton {
         action: String,
         position: (u32, u32),
+        click_count: u64,
     },
     Scroll {
         direction: String,
         position: (u32, u32),
     },
     Drag(u32, u32),
+    MouseMove(u32, u32),
+    Paste {
+        data: String,
+        phase: i64,
+    },
     FileDrop(String),
     FocusLost,
     FocusGained,
+    RefreshMode,
     Quit,
 }
 
@@ -31,7 +38,7 @@ impl UiCommand {
     pub async fn execute(self, nvim: &Neovim<Compat<ChildStdin>>) {
         match self {
             UiCommand::Resize { width, height } => nvim
-                .ui_try_resize(width.max(10) as i64, height.max(3) as i64)
+                .ui_try_resize(width as i64, height as i64)
                 .await
                 .expect("Resize failed"),
             UiCommand::Keyboard(input_command) => {
@@ -41,9 +48,15 @@ impl UiCommand {
             UiCommand::MouseButton {
                 action,
                 position: (grid_x, grid_y),
+                click_count,
             } => {
                 if EDITOR.lock().mouse_enabled {
-                    nvim.input_mouse("left", &action, "", 0, grid_y as i64, grid_x as i64)
+                    let modifier = if click_count > 1 {
+                        format!("{}-", click_count)
+                    } else {
+                        String::new()
+                    };
+                    nvim.input_mouse("left", &action, &modifier, 0, grid_y as i64, grid_x as i64)
                         .await
                         .expect("Mouse Input Failed");
                 }
@@ -65,6 +78,17 @@ impl UiCommand {
                         .expect("Mouse Drag Failed");
                 }
             }
+            UiCommand::MouseMove(grid_x, grid_y) => {
+                if EDITOR.lock().mouse_enabled {
+                    nvim.input_mouse("move", "", "", 0, grid_y as i64, grid_x as i64)
+                        .await
+                        .expect("Mouse Move Failed");
+                }
+            }
+            UiCommand::Paste { data, phase } => {
+                trace!("Paste phase {} sent", phase);
+                nvim.paste(&data, true, phase).await.expect("Paste Failed");
+            }
             UiCommand::FocusLost => nvim
                 .command("if exists('#FocusLost') | doautocmd <nomodeline> FocusLost | endif")
                 .await
@@ -73,6 +97,10 @@ impl UiCommand {
                 .command("if exists('#FocusGained') | doautocmd <nomodeline> FocusGained | endif")
                 .await
                 .expect("Focus Gained Failed"),
+            UiCommand::RefreshMode => {
+                let mode_status = nvim.get_mode().await.expect("Get Mode Failed");
+                EDITOR.lock().refresh_mode(mode_status);
+            }
             UiCommand::Quit => {
                 nvim.command("qa!").await.ok(); // Ignoring result as it won't succeed since the app closed.
             }
@@ -25,6 +25,7 @@ impl CursorShape {
 
 #[derive(Default, Debug, Clone, PartialEq)]
 pub struct CursorMode {
+    pub name: Option<String>,
     pub shape: Option<CursorShape>,
     pub style_id: Option<u64>,
     pub cell_percentage: Option<f32>,
@@ -85,6 +86,7 @@ impl Cursor {
 
     pub fn change_mode(&mut self, cursor_mode: &CursorMode, styles: &HashMap<u64, Arc<Style>>) {
         let CursorMode {
+            name: _,
             shape,
             style_id,
             cell_percentage,
@@ -146,6 +148,12 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_from_type_name_unknown() {
+        assert_eq!(CursorShape::from_type_name("underline"), None);
+        assert_eq!(CursorShape::from_type_name(""), None);
+    }
+
     #[test]
     fn test_foreground() {
         let mut cursor = Cursor::new();
@@ -193,6 +201,7 @@ mod tests {
     #[test]
     fn test_change_mode() {
         let cursor_mode = CursorMode {
+            name: Some("normal".to_string()),
             shape: Some(CursorShape::Horizontal),
             style_id: Some(1),
             cell_percentage: Some(100.0),
@@ -214,6 +223,7 @@ mod tests {
         assert_eq!(cursor.blinkoff, Some(1));
 
         let cursor_mode_with_none = CursorMode {
+            name: None,
             shape: None,
             style_id: None,
             cell_percentage: None,
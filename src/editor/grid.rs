@@ -11,6 +11,7 @@ pub struct CharacterGrid {
     pub should_clear: bool,
 
     dirty: Vec<bool>,
+    versions: Vec<u64>,
     characters: Vec<GridCell>,
 }
 
@@ -21,6 +22,7 @@ impl CharacterGrid {
         CharacterGrid {
             characters: vec![None; cell_count],
             dirty: vec![true; cell_count],
+            versions: vec![0; cell_count],
             width,
             height,
             should_clear: true,
@@ -38,6 +40,7 @@ impl CharacterGrid {
         trace!("Editor cleared");
         self.set_characters_all(None);
         self.set_dirty_all(true);
+        self.bump_versions_all();
         self.should_clear = true;
     }
 
@@ -58,6 +61,23 @@ impl CharacterGrid {
             .map(move |idx| &mut self.characters[idx])
     }
 
+    // Up to `length` cells starting at (x, y), clipped to the row and marked as changed
+    pub fn row_span_mut(&mut self, x: u64, y: u64, length: usize) -> Option<&mut [GridCell]> {
+        let start = self.cell_index(x, y)?;
+        let end = start + (length as u64).min(self.width - x) as usize;
+        for index in start..end {
+            self.dirty[index] = true;
+            self.versions[index] += 1;
+        }
+        Some(&mut self.characters[start..end])
+    }
+
+    pub fn cell_version(&self, x: u64, y: u64) -> u64 {
+        self.cell_index(x, y)
+            .map(|idx| self.versions[idx])
+            .unwrap_or(0)
+    }
+
     pub fn is_dirty_cell(&self, x: u64, y: u64) -> bool {
         if let Some(idx) = self.cell_index(x, y) {
             self.dirty[idx]
@@ -69,6 +89,7 @@ impl CharacterGrid {
     pub fn set_dirty_cell(&mut self, x: u64, y: u64) {
         if let Some(idx) = self.cell_index(x, y) {
             self.dirty[idx] = true;
+            self.versions[idx] += 1;
         }
     }
 
@@ -78,6 +99,13 @@ impl CharacterGrid {
             .resize_with((self.width * self.height) as usize, || value);
     }
 
+    fn bump_versions_all(&mut self) {
+        self.versions.resize((self.width * self.height) as usize, 0);
+        for version in self.versions.iter_mut() {
+            *version += 1;
+        }
+    }
+
     pub fn set_characters_all(&mut self, value: GridCell) {
         self.characters.clear();
         self.characters
@@ -86,6 +114,16 @@ impl CharacterGrid {
             });
     }
 
+    // The dimensions implied by the allocated cells rather than the width and height fields
+    pub fn allocated_size(&self) -> (u64, u64) {
+        let cell_count = self.characters.len() as u64;
+        if self.width == 0 {
+            (0, 0)
+        } else {
+            (self.width, cell_count / self.width)
+        }
+    }
+
     pub fn rows(&self) -> impl Iterator<Item = &[GridCell]> {
         (0..self.height).map(move |row| {
             &self.characters[(row * self.width) as usize..((row + 1) * self.width) as usize]
@@ -203,6 +241,21 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_row_span_mut() {
+        let mut character_grid = CharacterGrid::new((10, 2));
+
+        // RUN FUNCTION
+        character_grid.set_dirty_all(false);
+        assert_eq!(character_grid.row_span_mut(7, 1, 5).unwrap().len(), 3);
+        assert!(!character_grid.is_dirty_cell(6, 1));
+        assert!(character_grid.is_dirty_cell(7, 1));
+        assert!(character_grid.is_dirty_cell(9, 1));
+        assert_eq!(character_grid.cell_version(9, 1), 1);
+        assert!(character_grid.row_span_mut(10, 1, 5).is_none());
+        assert!(character_grid.row_span_mut(0, 2, 5).is_none());
+    }
+
     #[test]
     fn test_is_dirty_cell() {
         let context = Context::new();
@@ -224,6 +277,19 @@ mod tests {
         assert!(character_grid.dirty[context.index]);
     }
 
+    #[test]
+    fn test_cell_version() {
+        let context = Context::new();
+        let mut character_grid = CharacterGrid::new(context.size);
+
+        // RUN FUNCTION
+        character_grid.set_dirty_cell(context.x, context.y);
+        assert_eq!(character_grid.cell_version(context.x, context.y), 1);
+        character_grid.clear();
+        assert_eq!(character_grid.cell_version(context.x, context.y), 2);
+        assert_eq!(character_grid.cell_version(context.size.0, 0), 0);
+    }
+
     #[test]
     fn test_set_dirty_all() {
         let context = Context::new();
@@ -269,7 +335,7 @@ mod tests {
 
         assert_eq!(character_grid.width, context.size.0);
         assert_eq!(character_grid.height, context.size.1);
-        assert_eq!(character_grid.should_clear, true);
+        assert!(character_grid.should_clear);
         assert_eq!(character_grid.characters, vec![None; context.area]);
         assert_eq!(character_grid.dirty, vec![true; context.area]);
     }
@@ -297,11 +363,26 @@ mod tests {
 
         assert_eq!(character_grid.width, width);
         assert_eq!(character_grid.height, height);
-        assert_eq!(character_grid.should_clear, true);
+        assert!(character_grid.should_clear);
         assert_eq!(character_grid.characters, vec![None; new_area]);
         assert_eq!(character_grid.dirty, vec![true; new_area]);
     }
 
+    #[test]
+    fn test_allocated_size() {
+        let context = Context::new();
+        let mut character_grid = CharacterGrid::new(context.size);
+        character_grid.height += 1;
+
+        // RUN FUNCTION
+        assert_eq!(character_grid.allocated_size(), context.size);
+        character_grid.resize(context.size.0, context.size.1 + 1);
+        assert_eq!(
+            character_grid.allocated_size(),
+            (context.size.0, context.size.1 + 1)
+        );
+    }
+
     #[test]
     fn test_rows() {
         let context = Context::new();
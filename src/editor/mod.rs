@@ -2,25 +2,116 @@ mod cursor;
 mod grid;
 mod style;
 
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::sync::Arc;
 
-use log::trace;
+use log::{error, trace};
 use parking_lot::Mutex;
-use skulpin::skia_safe::colors;
+use rmpv::Value;
+use skulpin::skia_safe::{colors, Color4f};
 use unicode_segmentation::UnicodeSegmentation;
 
-use crate::bridge::{EditorMode, GridLineCell, GuiOption, RedrawEvent};
+use crate::bridge::{
+    parse_mode_status, EditorMode, GridLineCell, GuiOption, RedrawEvent, StyledContent,
+    WindowAnchor,
+};
 use crate::redraw_scheduler::REDRAW_SCHEDULER;
+use crate::renderer::cursor_renderer::animation_utils::{ease_linear, ease_out_cubic};
 use crate::window::window_geometry_or_default;
 pub use cursor::{Cursor, CursorMode, CursorShape};
-pub use grid::CharacterGrid;
-pub use style::{Colors, Style};
+pub use grid::{CharacterGrid, GridCell};
+pub use style::{
+    blend_color, ColorFilter, Colors, DecorationSet, ResolvedStyle, Style, UnderlineStyle,
+};
+
+// Grid 1 is the global grid which every other grid is positioned relative to
+pub const MAIN_GRID: u64 = 1;
+
+// Presses on the same cell within this window count towards a double or triple click
+const MULTI_CLICK_TIMEOUT_MS: u64 = 500;
+const GRID_POSITION_EPSILON: f32 = 0.01;
+const DEFAULT_MESSAGE_HISTORY_CAPACITY: usize = 200;
 
 lazy_static! {
     pub static ref EDITOR: Arc<Mutex<Editor>> = Arc::new(Mutex::new(Editor::new()));
 }
 
+// Control characters have no glyph, so they are shown in caret notation like ^A
+fn sanitize_cell_char(character: char) -> Option<String> {
+    match character {
+        '\u{0}'..='\u{1f}' => Some(format!("^{}", (character as u8 + b'@') as char)),
+        '\u{7f}' => Some("^?".to_string()),
+        _ => None,
+    }
+}
+
+fn cell_text(grapheme: &str) -> String {
+    let mut characters = grapheme.chars();
+    match (characters.next(), characters.next()) {
+        (Some(character), None) => {
+            sanitize_cell_char(character).unwrap_or_else(|| grapheme.to_string())
+        }
+        _ => grapheme.to_string(),
+    }
+}
+
+fn initial_default_style() -> Arc<Style> {
+    Arc::new(Style::new(Colors::new(
+        Some(colors::WHITE),
+        Some(colors::BLACK),
+        Some(colors::GREY),
+    )))
+}
+
+// Combining diacritical marks, which draw on top of the character before them
+fn starts_with_combining_mark(text: &str) -> bool {
+    matches!(
+        text.chars().next(),
+        Some('\u{0300}'..='\u{036F}')
+            | Some('\u{1AB0}'..='\u{1AFF}')
+            | Some('\u{1DC0}'..='\u{1DFF}')
+            | Some('\u{20D0}'..='\u{20FF}')
+            | Some('\u{FE20}'..='\u{FE2F}')
+    )
+}
+
+// Splits a "Family:hSize" font option into the family and its size, if one is given
+fn parse_font_setting(setting: &str) -> Option<(String, Option<f32>)> {
+    let mut parts = setting.split(':');
+    let family = parts.next().filter(|family| !family.is_empty())?;
+    let size = parts
+        .filter(|part| part.starts_with('h') && part.len() > 1)
+        .find_map(|part| part[1..].parse::<f32>().ok());
+    Some((family.to_string(), size))
+}
+
+// The width up to the last drawn cell in a row and the style most of its drawn cells use
+fn row_content(row: &[GridCell]) -> (usize, Option<Arc<Style>>) {
+    let content_width = row
+        .iter()
+        .rposition(|cell| cell.is_some())
+        .map(|index| index + 1)
+        .unwrap_or(0);
+
+    let mut style_counts: Vec<(&Option<Arc<Style>>, usize)> = Vec::new();
+    for (_, style) in row.iter().flatten() {
+        match style_counts
+            .iter_mut()
+            .find(|(counted, _)| *counted == style)
+        {
+            Some((_, count)) => *count += 1,
+            None => style_counts.push((style, 1)),
+        }
+    }
+
+    let dominant_style = style_counts
+        .into_iter()
+        .max_by_key(|(_, count)| *count)
+        .and_then(|(style, _)| style.clone());
+
+    (content_width, dominant_style)
+}
+
 #[derive(new, Debug, Clone)]
 pub struct DrawCommand {
     pub text: String,
@@ -29,47 +120,372 @@ pub struct DrawCommand {
     pub style: Option<Arc<Style>>,
 }
 
+// Which ext_* UI features Neovim is asked for when attaching. The editor only understands the
+// events these enable, so it decides them rather than the bridge
+#[derive(new, Debug, Clone, PartialEq)]
+pub struct AttachOptions {
+    pub linegrid: bool,
+    pub multigrid: bool,
+    pub popupmenu: bool,
+    pub cmdline: bool,
+    pub messages: bool,
+}
+
+impl Default for AttachOptions {
+    fn default() -> Self {
+        AttachOptions::new(true, false, false, false, false)
+    }
+}
+
+impl AttachOptions {
+    pub fn to_value_map(&self) -> Vec<(Value, Value)> {
+        vec![
+            ("rgb", true),
+            ("ext_linegrid", self.linegrid),
+            ("ext_multigrid", self.multigrid),
+            ("ext_popupmenu", self.popupmenu),
+            ("ext_cmdline", self.cmdline),
+            ("ext_messages", self.messages),
+        ]
+        .into_iter()
+        .map(|(name, enabled)| (Value::from(name), Value::from(enabled)))
+        .collect()
+    }
+}
+
+// Curves for smooth scrolling, picked with the scroll_easing setting
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum EasingKind {
+    Linear,
+    OutCubic,
+}
+
+impl EasingKind {
+    fn apply(self, t: f32) -> f32 {
+        match self {
+            EasingKind::Linear => ease_linear(t),
+            EasingKind::OutCubic => ease_out_cubic(t),
+        }
+    }
+}
+
+#[derive(new, Debug, Clone, PartialEq)]
+pub struct Viewport {
+    pub top_line: u64,
+    pub bottom_line: u64,
+    pub current_line: u64,
+    pub current_column: u64,
+    pub scroll_delta: i64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Script {
+    Other,
+    Cjk,
+    Emoji,
+}
+
+impl Script {
+    // Whitespace has no script of its own and is shaped with whatever surrounds it
+    fn of(character: &str) -> Option<Script> {
+        let character = character.chars().next()?;
+        if character.is_whitespace() {
+            return None;
+        }
+
+        Some(match character as u32 {
+            0x1100..=0x11FF
+            | 0x2E80..=0xA4CF
+            | 0xAC00..=0xD7A3
+            | 0xF900..=0xFAFF
+            | 0xFE30..=0xFE4F
+            | 0xFF00..=0xFF60
+            | 0xFFE0..=0xFFE6
+            | 0x20000..=0x3FFFD => Script::Cjk,
+            0x2600..=0x27BF | 0x1F000..=0x1FAFF => Script::Emoji,
+            _ => Script::Other,
+        })
+    }
+}
+
+// Cells a grapheme takes up on screen. Neovim does the real measuring for the grid, this is
+// only for text the GUI lays out itself
+fn display_width(grapheme: &str) -> u64 {
+    match Script::of(grapheme) {
+        Some(Script::Cjk) | Some(Script::Emoji) => 2,
+        _ => 1,
+    }
+}
+
+impl DrawCommand {
+    #[allow(dead_code)]
+    pub fn resolved_style(&self, default_colors: &Colors) -> ResolvedStyle {
+        match &self.style {
+            Some(style) => style.resolve(default_colors),
+            None => Style::new(default_colors.clone()).resolve(default_colors),
+        }
+    }
+
+    // Unstyled commands use the default style, which never decorates its text
+    #[allow(dead_code)]
+    pub fn decorations(&self) -> DecorationSet {
+        self.style
+            .as_ref()
+            .map(|style| style.decorations())
+            .unwrap_or_default()
+    }
+
+    // The hyperlink the whole run points to, so the renderer can make it clickable
+    #[allow(dead_code)]
+    pub fn url(&self) -> Option<&str> {
+        self.style.as_ref().and_then(|style| style.url.as_deref())
+    }
+
+    // Breaks the command wherever the text changes between Latin-like, CJK and emoji so each
+    // piece can be shaped on its own. Every grapheme in a command fills exactly one cell
+    #[allow(dead_code)]
+    pub fn split_by_script(&self) -> Vec<DrawCommand> {
+        let (start_x, y) = self.grid_position;
+        let mut commands = Vec::new();
+        let mut current: Option<(Option<Script>, DrawCommand)> = None;
+
+        for (index, character) in self.text.graphemes(true).enumerate() {
+            let script = Script::of(character);
+            match &mut current {
+                Some((run_script, command))
+                    if script.is_none() || run_script.is_none() || *run_script == script =>
+                {
+                    if run_script.is_none() {
+                        *run_script = script;
+                    }
+                    command.text.push_str(character);
+                    command.cell_width += 1;
+                }
+                _ => {
+                    if let Some((_, command)) = current.take() {
+                        commands.push(command);
+                    }
+                    current = Some((
+                        script,
+                        DrawCommand::new(
+                            character.to_string(),
+                            1,
+                            (start_x + index as u64, y),
+                            self.style.clone(),
+                        ),
+                    ));
+                }
+            }
+        }
+
+        if let Some((_, command)) = current {
+            commands.push(command);
+        }
+        commands
+    }
+}
+
 pub struct Editor {
     pub grid: CharacterGrid,
+    pub grids: HashMap<u64, CharacterGrid>,
+    pub grid_positions: HashMap<u64, (u64, u64)>,
+    pub grid_render_positions: HashMap<u64, (f32, f32)>,
+    pub grid_order: Vec<u64>,
+    pub float_grids: HashSet<u64>,
+    pub grid_opacity: HashMap<u64, f32>,
+    pub grid_blend: HashMap<u64, u8>,
+    pub grid_backgrounds: HashMap<u64, Color4f>,
+    pub min_grid_size: (u64, u64),
+    pub viewports: HashMap<u64, Viewport>,
+    pub focused_grid: u64,
+    pub attach_options: AttachOptions,
     pub title: String,
+    pub working_dir: Option<String>,
     pub mouse_enabled: bool,
+    pub mouse_move_enabled: bool,
+    pub last_mouse_move: Option<(u64, u64)>,
+    pub suspended: bool,
+    pub pending_clear: bool,
+    pub fill_row_gaps: bool,
+    pub debug_overlay: bool,
+    pub previous_snapshot: Vec<Vec<GridCell>>,
+    pub last_scroll: Option<i64>,
+    pub max_scroll_animation_distance: Option<u64>,
+    pub scroll_easing: EasingKind,
+    pub last_mouse_press: Option<((u64, u64), String, u64)>,
+    pub click_count: u64,
     pub guifont: Option<String>,
+    pub guifont_wide: Option<String>,
+    pub font_fallbacks: Vec<String>,
+    pub linespace: i64,
+    pub ligatures_enabled: bool,
     pub cursor: Cursor,
+    pub previous_cursor_position: Option<(u64, (u64, u64))>,
     pub default_style: Arc<Style>,
+    pub background_changed: bool,
+    pub cursor_snap_requested: bool,
+    pub color_filter: ColorFilter,
+    pub color_levels: Option<u8>,
     pub defined_styles: HashMap<u64, Arc<Style>>,
+    pub hl_groups: HashMap<String, u64>,
     pub previous_style: Option<Arc<Style>>,
+    pub resolved_style: Option<(u64, Option<Arc<Style>>)>,
+    pub style_lookups: u64,
     pub mode_list: Vec<CursorMode>,
+    pub mode_info: HashMap<String, CursorMode>,
+    pub wildmenu_items: Vec<String>,
+    pub wildmenu_selected: Option<usize>,
+    pub message_history: VecDeque<(Style, String)>,
+    pub message_history_capacity: usize,
     pub current_mode: EditorMode,
+    pub out_of_bounds_lines: u64,
+    pub pending_grid_lines: VecDeque<(u64, u64, u64, VecDeque<GridLineCell>)>,
+    pub pending_line_column: Option<u64>,
 }
 
 impl Editor {
     pub fn new() -> Editor {
+        let (width, height) = window_geometry_or_default();
+        Editor::new_headless(width, height)
+    }
+
+    // An editor with a fixed main grid size that doesn't look at the window settings, for
+    // rendering tests
+    pub fn new_headless(width: u64, height: u64) -> Editor {
         Editor {
-            grid: CharacterGrid::new(window_geometry_or_default()),
+            grid: CharacterGrid::new((width, height)),
+            grids: HashMap::new(),
+            grid_positions: HashMap::new(),
+            grid_render_positions: HashMap::new(),
+            grid_order: Vec::new(),
+            float_grids: HashSet::new(),
+            grid_opacity: HashMap::new(),
+            grid_blend: HashMap::new(),
+            grid_backgrounds: HashMap::new(),
+            min_grid_size: (1, 1),
+            viewports: HashMap::new(),
+            focused_grid: MAIN_GRID,
+            attach_options: AttachOptions::default(),
             title: "Neovide".to_string(),
+            working_dir: None,
             mouse_enabled: true,
+            mouse_move_enabled: false,
+            last_mouse_move: None,
+            suspended: false,
+            pending_clear: false,
+            fill_row_gaps: false,
+            debug_overlay: false,
+            previous_snapshot: Vec::new(),
+            last_scroll: None,
+            max_scroll_animation_distance: None,
+            scroll_easing: EasingKind::OutCubic,
+            last_mouse_press: None,
+            click_count: 0,
             guifont: None,
+            guifont_wide: None,
+            font_fallbacks: Vec::new(),
+            linespace: 0,
+            ligatures_enabled: true,
             cursor: Cursor::new(),
-            default_style: Arc::new(Style::new(Colors::new(
-                Some(colors::WHITE),
-                Some(colors::BLACK),
-                Some(colors::GREY),
-            ))),
+            previous_cursor_position: None,
+            default_style: initial_default_style(),
+            background_changed: false,
+            cursor_snap_requested: false,
+            color_filter: ColorFilter::default(),
+            color_levels: None,
             defined_styles: HashMap::new(),
+            hl_groups: HashMap::new(),
             previous_style: None,
+            resolved_style: None,
+            style_lookups: 0,
             mode_list: Vec::new(),
+            mode_info: HashMap::new(),
+            wildmenu_items: Vec::new(),
+            wildmenu_selected: None,
+            message_history: VecDeque::new(),
+            message_history_capacity: DEFAULT_MESSAGE_HISTORY_CAPACITY,
             current_mode: EditorMode::Unknown(String::from("")),
+            out_of_bounds_lines: 0,
+            pending_grid_lines: VecDeque::new(),
+            pending_line_column: None,
         }
     }
 
+    // The arguments for nvim_ui_attach: the main grid size and the options map
+    pub fn attach(&mut self, options: AttachOptions) -> Vec<Value> {
+        trace!("Attaching with {:?}", &options);
+        let arguments = vec![
+            Value::from(self.grid.width),
+            Value::from(self.grid.height),
+            Value::Map(options.to_value_map()),
+        ];
+        self.attach_options = options;
+        arguments
+    }
+
+    // Forgets everything the previous Neovim process set up so a restarted one can attach to
+    // the same editor. The main grid keeps its size and GUI side preferences are left alone
+    #[allow(dead_code)]
+    pub fn reset(&mut self) {
+        trace!("Editor reset");
+        self.grid.clear();
+        self.grids.clear();
+        self.grid_positions.clear();
+        self.grid_render_positions.clear();
+        self.grid_order.clear();
+        self.float_grids.clear();
+        self.grid_opacity.clear();
+        self.grid_blend.clear();
+        self.grid_backgrounds.clear();
+        self.viewports.clear();
+        self.focused_grid = MAIN_GRID;
+        self.title = "Neovide".to_string();
+        self.working_dir = None;
+        self.mouse_enabled = true;
+        self.mouse_move_enabled = false;
+        self.last_mouse_move = None;
+        self.pending_clear = false;
+        self.previous_snapshot.clear();
+        self.last_scroll = None;
+        self.guifont = None;
+        self.guifont_wide = None;
+        self.linespace = 0;
+        self.cursor = Cursor::new();
+        self.previous_cursor_position = None;
+        self.default_style = initial_default_style();
+        self.background_changed = true;
+        self.defined_styles.clear();
+        self.hl_groups.clear();
+        self.previous_style = None;
+        self.resolved_style = None;
+        self.mode_list.clear();
+        self.mode_info.clear();
+        self.wildmenu_items.clear();
+        self.wildmenu_selected = None;
+        self.message_history.clear();
+        self.current_mode = EditorMode::Unknown(String::from(""));
+        self.pending_grid_lines.clear();
+        self.pending_line_column = None;
+    }
+
     pub fn handle_redraw_event(&mut self, event: RedrawEvent) {
         match event {
             RedrawEvent::SetTitle { title } => self.title = title,
-            RedrawEvent::ModeInfoSet { cursor_modes } => self.mode_list = cursor_modes,
+            RedrawEvent::ModeInfoSet { cursor_modes } => {
+                let named_modes = cursor_modes
+                    .iter()
+                    .filter_map(|mode| mode.name.clone().map(|name| (name, mode.clone())))
+                    .collect();
+                self.set_mode_info(named_modes);
+                self.mode_list = cursor_modes;
+            }
             RedrawEvent::OptionSet { gui_option } => self.set_option(gui_option),
             RedrawEvent::ModeChange { mode, mode_index } => {
-                if let Some(cursor_mode) = self.mode_list.get(mode_index as usize) {
-                    self.cursor.change_mode(cursor_mode, &self.defined_styles);
+                if let Some(cursor_mode) = self.mode_list.get(mode_index as usize).cloned() {
+                    match &cursor_mode.name {
+                        Some(name) if self.set_mode(name) => {}
+                        _ => self.cursor.change_mode(&cursor_mode, &self.defined_styles),
+                    }
                     self.current_mode = mode
                 }
             }
@@ -87,222 +503,4613 @@ impl Editor {
                 trace!("Cursor on");
                 self.cursor.enabled = true;
             }
+            RedrawEvent::Suspend => self.set_suspended(true),
             RedrawEvent::Flush => {
-                trace!("Image flushed");
+                self.flush();
                 REDRAW_SCHEDULER.queue_next_frame();
             }
-            RedrawEvent::Resize { width, height, .. } => self.grid.resize(width, height),
-            RedrawEvent::DefaultColorsSet { colors } => {
-                self.default_style = Arc::new(Style::new(colors))
-            }
+            RedrawEvent::Resize {
+                grid,
+                width,
+                height,
+            } => self.resize_grid(grid, width, height),
+            RedrawEvent::DefaultColorsSet { colors } => self.set_default_colors(colors),
             RedrawEvent::HighlightAttributesDefine { id, style } => {
+                self.resolved_style = None;
                 self.defined_styles.insert(id, Arc::new(style));
             }
+            RedrawEvent::HighlightGroupSet { name, id } => self.set_hl_group(name, id),
             RedrawEvent::GridLine {
+                grid,
                 row,
                 column_start,
                 cells,
-                ..
-            } => self.draw_grid_line(row, column_start, cells),
-            RedrawEvent::Clear { .. } => self.grid.clear(),
-            RedrawEvent::CursorGoto { row, column, .. } => self.cursor.position = (row, column),
+            } => self.draw_grid_line(grid, row, column_start, cells),
+            RedrawEvent::Clear { grid } => {
+                if let Some(grid) = self.get_grid_mut(grid) {
+                    grid.clear();
+                    self.pending_clear = true;
+                }
+            }
+            RedrawEvent::CursorGoto { grid, row, column } => {
+                self.set_cursor_position(grid, (row, column))
+            }
             RedrawEvent::Scroll {
+                grid,
                 top,
                 bottom,
                 left,
                 right,
                 rows,
                 columns,
+            } => self.scroll_region(grid, top, bottom, left, right, rows, columns),
+            RedrawEvent::WindowPosition {
+                grid,
+                start_row,
+                start_column,
+                ..
+            } => {
+                self.float_grids.remove(&grid);
+                self.set_grid_position(grid, (start_column, start_row));
+            }
+            RedrawEvent::WindowFloatPosition {
+                grid,
+                anchor,
+                anchor_grid,
+                anchor_row,
+                anchor_column,
+                ..
+            } => self.set_float_position(grid, anchor, anchor_grid, anchor_row, anchor_column),
+            RedrawEvent::WindowHide { grid } => self.grid_order.retain(|id| *id != grid),
+            RedrawEvent::WindowClose { grid } => self.close_grid(grid),
+            RedrawEvent::WildmenuShow { items } => self.show_wildmenu(items),
+            RedrawEvent::WildmenuSelect { selected } => self.select_wildmenu(selected),
+            RedrawEvent::WildmenuHide => self.hide_wildmenu(),
+            RedrawEvent::MessageShow {
+                content,
+                replace_last,
                 ..
-            } => self.scroll_region(top, bottom, left, right, rows, columns),
+            } => self.record_message(content, replace_last),
+            RedrawEvent::WindowViewport {
+                grid,
+                top_line,
+                bottom_line,
+                current_line,
+                current_column,
+                scroll_delta,
+            } => self.set_viewport(
+                grid,
+                Viewport::new(
+                    top_line,
+                    bottom_line,
+                    current_line,
+                    current_column,
+                    scroll_delta,
+                ),
+            ),
             _ => {}
         };
     }
 
-    pub fn build_draw_commands(&mut self) -> (Vec<DrawCommand>, bool) {
-        let mut draw_commands = Vec::new();
+    pub fn get_grid(&self, grid: u64) -> Option<&CharacterGrid> {
+        if grid == MAIN_GRID {
+            Some(&self.grid)
+        } else {
+            self.grids.get(&grid)
+        }
+    }
 
-        for (row_index, row) in self.grid.rows().enumerate() {
-            let mut command = None;
+    pub fn get_grid_mut(&mut self, grid: u64) -> Option<&mut CharacterGrid> {
+        if grid == MAIN_GRID {
+            Some(&mut self.grid)
+        } else {
+            self.grids.get_mut(&grid)
+        }
+    }
 
-            fn add_command(commands_list: &mut Vec<DrawCommand>, command: Option<DrawCommand>) {
-                if let Some(command) = command {
-                    commands_list.push(command);
-                }
-            }
+    pub fn set_mode_info(&mut self, modes: HashMap<String, CursorMode>) {
+        trace!("Mode info set for {} modes", modes.len());
+        self.mode_info = modes;
+    }
 
-            fn command_matches(command: &Option<DrawCommand>, style: &Option<Arc<Style>>) -> bool {
-                match command {
-                    Some(command) => &command.style == style,
-                    None => true,
-                }
+    // Updates the cursor from the mode table, returning false for modes it doesn't know about
+    pub fn set_mode(&mut self, name: &str) -> bool {
+        match self.mode_info.get(name) {
+            Some(cursor_mode) => {
+                self.cursor.change_mode(cursor_mode, &self.defined_styles);
+                true
             }
+            None => false,
+        }
+    }
 
-            fn add_character(
-                command: &mut Option<DrawCommand>,
-                character: &str,
-                row_index: u64,
-                col_index: u64,
-                style: Option<Arc<Style>>,
-            ) {
-                match command {
-                    Some(command) => {
-                        command.text.push_str(character);
-                        command.cell_width += 1;
-                    }
-                    None => {
-                        command.replace(DrawCommand::new(
-                            character.to_string(),
-                            1,
-                            (col_index, row_index),
-                            style,
-                        ));
-                    }
-                }
-            }
+    // The main grid size Neovim last reported, which status lines should align to
+    #[allow(dead_code)]
+    pub fn columns(&self) -> u64 {
+        self.grid.width
+    }
 
-            for (col_index, cell) in row.iter().enumerate() {
-                if let Some((character, style)) = cell {
-                    if character.is_empty() {
-                        add_character(
-                            &mut command,
-                            &" ",
-                            row_index as u64,
-                            col_index as u64,
-                            style.clone(),
-                        );
-                        add_command(&mut draw_commands, command);
-                        command = None;
-                    } else {
-                        if !command_matches(&command, &style) {
-                            add_command(&mut draw_commands, command);
-                            command = None;
-                        }
-                        add_character(
-                            &mut command,
-                            &character,
-                            row_index as u64,
-                            col_index as u64,
-                            style.clone(),
-                        );
-                    }
-                } else {
-                    if !command_matches(&command, &None) {
-                        add_command(&mut draw_commands, command);
-                        command = None;
-                    }
-                    add_character(&mut command, " ", row_index as u64, col_index as u64, None);
-                }
-            }
-            add_command(&mut draw_commands, command);
-        }
+    #[allow(dead_code)]
+    pub fn rows(&self) -> u64 {
+        self.grid.height
+    }
 
-        let should_clear = self.grid.should_clear;
-        let draw_commands = draw_commands
-            .into_iter()
-            .filter(|command| {
-                let (x, y) = command.grid_position;
-                let min = (x as i64 - 1).max(0) as u64;
-                let max = (x + command.cell_width + 1).min(self.grid.width);
+    // Pixel size of the main grid, with 'linespace' added to every row
+    #[allow(dead_code)]
+    pub fn pixel_height(&self, cell_height: f32) -> f32 {
+        self.grid.height as f32 * (cell_height + self.linespace as f32).max(0.0)
+    }
 
-                for char_index in min..max {
-                    if self.grid.is_dirty_cell(char_index, y) {
-                        return true;
-                    }
-                }
-                false
-            })
-            .collect::<Vec<DrawCommand>>();
+    #[allow(dead_code)]
+    pub fn pixel_width(&self, cell_width: f32) -> f32 {
+        self.grid.width as f32 * cell_width
+    }
 
-        self.grid.set_dirty_all(false);
-        self.grid.should_clear = false;
+    // Health check against the grid's width and height, which should always agree
+    #[allow(dead_code)]
+    pub fn grid_dimensions(&self) -> (u64, u64) {
+        self.grid.allocated_size()
+    }
 
-        trace!("Draw commands sent");
-        (draw_commands, should_clear)
+    #[allow(dead_code)]
+    pub fn set_min_grid_size(&mut self, min_width: u64, min_height: u64) {
+        self.min_grid_size = (min_width.max(1), min_height.max(1));
     }
 
-    fn draw_grid_line_cell(&mut self, row_index: u64, column_pos: &mut u64, cell: GridLineCell) {
-        let style = match cell.highlight_id {
-            Some(0) => None,
-            Some(style_id) => self.defined_styles.get(&style_id).cloned(),
-            None => self.previous_style.clone(),
-        };
+    // Resize requests are clamped so Neovim never ends up in a degenerate tiny grid
+    pub fn clamp_grid_size(&self, width: u64, height: u64) -> (u64, u64) {
+        let (min_width, min_height) = self.min_grid_size;
+        (width.max(min_width), height.max(min_height))
+    }
+
+    // The grid size to request from Neovim for a window of the given logical size
+    pub fn grid_size_for_window(
+        &self,
+        window_size: (u32, u32),
+        font_dimensions: (f32, f32),
+    ) -> (u64, u64) {
+        let (window_width, window_height) = window_size;
+        let (font_width, font_height) = font_dimensions;
+        // Add 1 here to make sure resizing doesn't change the grid size on startup
+        let width = ((window_width + 1) as f32 / font_width) as u64;
+        let height = ((window_height + 1) as f32 / font_height) as u64;
+        self.clamp_grid_size(width, height)
+    }
+
+    pub fn grid_position(&self, grid: u64) -> (u64, u64) {
+        self.grid_positions.get(&grid).cloned().unwrap_or((0, 0))
+    }
+
+    // Where a grid is currently drawn while it slides toward its position, in cells
+    #[allow(dead_code)]
+    pub fn grid_render_position(&self, grid: u64) -> (f32, f32) {
+        self.grid_render_positions
+            .get(&grid)
+            .cloned()
+            .unwrap_or_else(|| {
+                let (x, y) = self.grid_position(grid);
+                (x as f32, y as f32)
+            })
+    }
 
-        let mut text = cell.text;
+    // Moves every positioned grid a step toward its logical position. Grids start out where
+    // they were first placed, so only later moves slide. Returns whether any grid is still moving
+    #[allow(dead_code)]
+    pub fn animate_grid_positions(&mut self, dt: f32, speed: f32) -> bool {
+        let step = (dt * speed).clamp(0.0, 1.0);
+        let mut animating = false;
 
-        if let Some(times) = cell.repeat {
-            text = text.repeat(times as usize);
+        for (grid, &(x, y)) in self.grid_positions.iter() {
+            let target = (x as f32, y as f32);
+            let (current_x, current_y) = *self.grid_render_positions.entry(*grid).or_insert(target);
+            let next = (
+                current_x + (target.0 - current_x) * step,
+                current_y + (target.1 - current_y) * step,
+            );
+
+            let position = if (target.0 - next.0).abs() < GRID_POSITION_EPSILON
+                && (target.1 - next.1).abs() < GRID_POSITION_EPSILON
+            {
+                target
+            } else {
+                animating = true;
+                next
+            };
+            self.grid_render_positions.insert(*grid, position);
         }
 
-        if text.is_empty() {
-            if let Some(cell) = self.grid.get_cell_mut(*column_pos, row_index) {
-                *cell = Some(("".to_string(), style.clone()));
-            }
+        animating
+    }
 
-            self.grid.set_dirty_cell(*column_pos, row_index);
-            *column_pos += 1;
+    fn resize_grid(&mut self, grid: u64, width: u64, height: u64) {
+        if let Some(grid) = self.get_grid_mut(grid) {
+            grid.resize(width, height);
         } else {
-            for (i, character) in text.graphemes(true).enumerate() {
-                if let Some(cell) = self.grid.get_cell_mut(i as u64 + *column_pos, row_index) {
-                    *cell = Some((character.to_string(), style.clone()));
-                    self.grid.set_dirty_cell(*column_pos, row_index);
-                }
-            }
-            *column_pos += text.graphemes(true).count() as u64;
+            self.grids.insert(grid, CharacterGrid::new((width, height)));
+        }
+
+        if grid == MAIN_GRID {
+            self.reclamp_grid_positions();
         }
 
-        self.previous_style = style;
+        // Keep the cursor on screen until Neovim sends its new position
+        if grid == self.focused_grid {
+            let (x, y) = self.cursor.position;
+            self.cursor.position = (
+                x.min(width.saturating_sub(1)),
+                y.min(height.saturating_sub(1)),
+            );
+        }
     }
 
-    fn draw_grid_line(&mut self, row: u64, column_start: u64, cells: Vec<GridLineCell>) {
-        if row < self.grid.height {
-            let mut column_pos = column_start;
-            for cell in cells {
-                self.draw_grid_line_cell(row, &mut column_pos, cell);
+    fn set_grid_position(&mut self, grid: u64, position: (u64, u64)) {
+        trace!("Grid {} positioned at {:?}", grid, position);
+        self.grid_positions.insert(grid, position);
+        self.grid_order.retain(|id| *id != grid);
+        self.grid_order.push(grid);
+    }
+
+    // Pulls grids left beyond the edge of a shrunk main grid back so at least one cell shows
+    pub fn reclamp_grid_positions(&mut self) {
+        let max_x = self.grid.width.saturating_sub(1);
+        let max_y = self.grid.height.saturating_sub(1);
+
+        for (grid, position) in self.grid_positions.iter_mut() {
+            let (x, y) = *position;
+            if x > max_x || y > max_y {
+                trace!("Grid {} pulled back into view", grid);
+                *position = (x.min(max_x), y.min(max_y));
             }
-        } else {
-            println!("Draw command out of bounds");
         }
     }
 
-    fn scroll_region(&mut self, top: u64, bot: u64, left: u64, right: u64, rows: i64, cols: i64) {
-        let y_iter: Box<dyn Iterator<Item = i64>> = if rows > 0 {
-            Box::new((top as i64 + rows)..bot as i64)
-        } else {
-            Box::new((top as i64..(bot as i64 + rows)).rev())
+    fn set_float_position(
+        &mut self,
+        grid: u64,
+        anchor: WindowAnchor,
+        anchor_grid: u64,
+        anchor_row: u64,
+        anchor_column: u64,
+    ) {
+        let (anchor_x, anchor_y) = self.grid_position(anchor_grid);
+        let (width, height) = self
+            .get_grid(grid)
+            .map(|grid| (grid.width, grid.height))
+            .unwrap_or((0, 0));
+
+        let (left, top) = match anchor {
+            WindowAnchor::NorthWest => (anchor_column, anchor_row),
+            WindowAnchor::NorthEast => (anchor_column.saturating_sub(width), anchor_row),
+            WindowAnchor::SouthWest => (anchor_column, anchor_row.saturating_sub(height)),
+            WindowAnchor::SouthEast => (
+                anchor_column.saturating_sub(width),
+                anchor_row.saturating_sub(height),
+            ),
         };
 
-        for y in y_iter {
-            let dest_y = y - rows;
-            if dest_y >= 0 && dest_y < self.grid.height as i64 {
-                let x_iter: Box<dyn Iterator<Item = i64>> = if cols > 0 {
-                    Box::new((left as i64 + cols)..right as i64)
-                } else {
-                    Box::new((left as i64..(right as i64 + cols)).rev())
-                };
+        self.float_grids.insert(grid);
+        self.set_grid_position(grid, (anchor_x + left, anchor_y + top));
+    }
 
-                for x in x_iter {
-                    let dest_x = x - cols;
-                    let cell_data = self.grid.get_cell(x as u64, y as u64).cloned();
+    #[allow(dead_code)]
+    pub fn is_float(&self, grid: u64) -> bool {
+        self.float_grids.contains(&grid)
+    }
 
-                    if let Some(cell_data) = cell_data {
-                        if let Some(dest_cell) =
-                            self.grid.get_cell_mut(dest_x as u64, dest_y as u64)
-                        {
-                            *dest_cell = cell_data;
-                            self.grid.set_dirty_cell(dest_x as u64, dest_y as u64);
-                        }
-                    }
-                }
-            }
+    fn close_grid(&mut self, grid: u64) {
+        trace!("Grid {} closed", grid);
+        self.grids.remove(&grid);
+        self.grid_positions.remove(&grid);
+        self.grid_render_positions.remove(&grid);
+        self.float_grids.remove(&grid);
+        self.grid_order.retain(|id| *id != grid);
+        self.viewports.remove(&grid);
+        self.grid_opacity.remove(&grid);
+        self.grid_blend.remove(&grid);
+        self.grid_backgrounds.remove(&grid);
+    }
+
+    fn set_cursor_position(&mut self, grid: u64, position: (u64, u64)) {
+        self.previous_cursor_position = Some((self.focused_grid, self.cursor.position));
+        self.focused_grid = grid;
+        self.cursor.position = position;
+
+        // Folds and wrapping can make this approximate until the next win_viewport arrives
+        if let Some(viewport) = self.viewports.get_mut(&grid) {
+            let (_, y) = position;
+            viewport.current_line = viewport.top_line + y;
         }
-        trace!("Region scrolled");
     }
 
-    fn set_option(&mut self, gui_option: GuiOption) {
-        trace!("Option set {:?}", &gui_option);
-        if let GuiOption::GuiFont(guifont) = gui_option {
-            self.guifont = Some(guifont);
+    // The cursor as (grid, row, column). Neovim always reports the cursor relative to its own
+    // grid, so these are window-local and need the grid position added for the screen
+    #[allow(dead_code)]
+    pub fn cursor_local_position(&self) -> (u64, u64, u64) {
+        let (x, y) = self.cursor.position;
+        (self.focused_grid, y, x)
+    }
+
+    // Milliseconds from elapsed_ms, measured since the cursor last changed, until the blink
+    // next toggles its visibility. Follows the wait, on, off cycle of the cursor renderer
+    #[allow(dead_code)]
+    pub fn next_blink_transition_ms(&self, elapsed_ms: u64) -> Option<u64> {
+        if self.cursor.blinkwait == Some(0) {
+            return None;
+        }
+        let blinkwait = self.cursor.blinkwait.unwrap_or(0);
+        let blinkon = self.cursor.blinkon.filter(|millis| *millis > 0)?;
+        let blinkoff = self.cursor.blinkoff.filter(|millis| *millis > 0)?;
+
+        if elapsed_ms < blinkwait {
+            return Some(blinkwait - elapsed_ms);
+        }
+
+        let cycle_position = (elapsed_ms - blinkwait) % (blinkon + blinkoff);
+        if cycle_position < blinkon {
+            Some(blinkon - cycle_position)
+        } else {
+            Some(blinkon + blinkoff - cycle_position)
         }
     }
+
+    pub fn set_viewport(&mut self, grid: u64, viewport: Viewport) {
+        trace!("Grid {} viewport {:?}", grid, &viewport);
+        if viewport.scroll_delta != 0 {
+            self.last_scroll = Some(viewport.scroll_delta);
+            if !self.should_animate_last_scroll() {
+                self.snap_cursor();
+            }
+        }
+        self.viewports.insert(grid, viewport);
+    }
+
+    // Lets windows fade in and out. The opacity is multiplied into everything drawn for the grid
+    #[allow(dead_code)]
+    pub fn set_grid_opacity(&mut self, grid: u64, opacity: f32) {
+        self.grid_opacity.insert(grid, opacity.clamp(0.0, 1.0));
+    }
+
+    pub fn grid_opacity(&self, grid: u64) -> f32 {
+        self.grid_opacity.get(&grid).cloned().unwrap_or(1.0)
+    }
+
+    // A float's winblend, used for cells whose highlight doesn't carry a blend of its own
+    #[allow(dead_code)]
+    pub fn set_grid_blend(&mut self, grid: u64, blend: u8) {
+        self.grid_blend.insert(grid, blend.min(100));
+    }
+
+    pub fn grid_blend(&self, grid: u64) -> u8 {
+        self.grid_blend.get(&grid).cloned().unwrap_or(0)
+    }
+
+    #[allow(dead_code)]
+    pub fn set_max_scroll_animation_distance(&mut self, rows: u64) {
+        self.max_scroll_animation_distance = Some(rows);
+    }
+
+    pub fn set_scroll_easing(&mut self, kind: EasingKind) {
+        self.scroll_easing = kind;
+    }
+
+    // Maps how far through a scroll animation we are, from 0 to 1, onto how far the content
+    // should have moved
+    #[allow(dead_code)]
+    pub fn scroll_ease(&self, t: f32) -> f32 {
+        self.scroll_easing.apply(t.clamp(0.0, 1.0))
+    }
+
+    // Scrolls further than the maximum distance snap straight to their destination
+    #[allow(dead_code)]
+    pub fn should_animate_last_scroll(&self) -> bool {
+        match (self.last_scroll, self.max_scroll_animation_distance) {
+            (Some(distance), Some(max_distance)) => distance.unsigned_abs() <= max_distance,
+            (Some(_), None) => true,
+            (None, _) => false,
+        }
+    }
+
+    #[allow(dead_code)]
+    pub fn viewport(&self, grid: u64) -> Option<&Viewport> {
+        self.viewports.get(&grid)
+    }
+
+    // Visible grids from bottom to top. The main grid is painted first unless it was positioned
+    #[allow(dead_code)]
+    pub fn active_grids(&self) -> Vec<u64> {
+        let mut grids = Vec::with_capacity(self.grid_order.len() + 1);
+        if !self.grid_order.contains(&MAIN_GRID) {
+            grids.push(MAIN_GRID);
+        }
+        grids.extend(self.grid_order.iter().cloned());
+        grids
+    }
+
+    // The (columns, rows) needed to hold every visible grid where it is placed, which floats
+    // hanging off the edge can push past the main grid
+    #[allow(dead_code)]
+    pub fn total_bounds(&self) -> (u64, u64) {
+        self.active_grids()
+            .into_iter()
+            .filter_map(|id| {
+                let grid = self.get_grid(id)?;
+                let (x, y) = self.grid_position(id);
+                Some((x + grid.width, y + grid.height))
+            })
+            .fold((0, 0), |(columns, rows), (right, bottom)| {
+                (columns.max(right), rows.max(bottom))
+            })
+    }
+
+    // A lone grid is never dimmed, even while Neovim reports it as unfocused
+    #[allow(dead_code)]
+    pub fn should_dim_grid(&self, grid: u64) -> bool {
+        grid != self.focused_grid && self.active_grids().len() > 1
+    }
+
+    #[allow(dead_code)]
+    pub fn grid_at_pixel(&self, x: f32, y: f32, font_width: f32, font_height: f32) -> Option<u64> {
+        self.active_grids().into_iter().rev().find(|grid_id| {
+            if let Some(grid) = self.get_grid(*grid_id) {
+                let (grid_x, grid_y) = self.grid_position(*grid_id);
+                let left = grid_x as f32 * font_width;
+                let top = grid_y as f32 * font_height;
+                let right = left + grid.width as f32 * font_width;
+                let bottom = top + grid.height as f32 * font_height;
+
+                x >= left && x < right && y >= top && y < bottom
+            } else {
+                false
+            }
+        })
+    }
+
+    #[allow(dead_code)]
+    pub fn pixel_to_grid_cell(
+        &self,
+        grid: u64,
+        x: f32,
+        y: f32,
+        font_width: f32,
+        font_height: f32,
+    ) -> (u64, u64) {
+        let (grid_x, grid_y) = self.grid_position(grid);
+        let (width, height) = self
+            .get_grid(grid)
+            .map(|grid| (grid.width, grid.height))
+            .unwrap_or((1, 1));
+
+        let column = (x / font_width - grid_x as f32).max(0.0) as u64;
+        let row = (y / font_height - grid_y as f32).max(0.0) as u64;
+
+        (
+            column.min(width.saturating_sub(1)),
+            row.min(height.saturating_sub(1)),
+        )
+    }
+
+    pub fn cell_style(&self, grid: u64, x: u64, y: u64) -> Arc<Style> {
+        match self.get_grid(grid).and_then(|grid| grid.get_cell(x, y)) {
+            Some(Some((_, Some(style)))) => style.clone(),
+            _ if self.grid_backgrounds.contains_key(&grid) => {
+                Arc::new(Style::new(self.grid_default_colors(grid)))
+            }
+            _ => self.default_style.clone(),
+        }
+    }
+
+    // A concrete style for a main grid cell with any blend already applied over the default
+    // background, so the renderer can draw it directly
+    #[allow(dead_code)]
+    pub fn resolve_cell_render_style(&self, row: u64, col: u64) -> Style {
+        let default_colors = &self.default_style.colors;
+        let mut style = self.cell_style(MAIN_GRID, col, row).as_ref().clone();
+
+        if style.blend > 0 {
+            let background = blend_color(
+                &style.background(default_colors),
+                &default_colors.background.clone().unwrap(),
+                style.blend,
+            );
+            if style.reverse {
+                style.colors.foreground = Some(background);
+            } else {
+                style.colors.background = Some(background);
+            }
+            style.blend = 0;
+        }
+
+        style
+    }
+
+    // Windows can override their background, for example through NormalFloat in winhighlight
+    #[allow(dead_code)]
+    pub fn set_grid_background(&mut self, grid: u64, background: Option<Color4f>) {
+        match background {
+            Some(background) => self.grid_backgrounds.insert(grid, background),
+            None => self.grid_backgrounds.remove(&grid),
+        };
+    }
+
+    // The default colors for a grid's unstyled cells
+    pub fn grid_default_colors(&self, grid: u64) -> Colors {
+        let mut colors = self.default_style.colors.clone();
+        if let Some(background) = self.grid_backgrounds.get(&grid) {
+            colors.background = Some(background.clone());
+        }
+        colors
+    }
+
+    #[allow(dead_code)]
+    pub fn composite_grid_background(&self, grid: u64, x: u64, y: u64) -> Color4f {
+        let default_colors = &self.default_style.colors;
+        let style = self.cell_style(grid, x, y);
+        let background = style.background(&self.grid_default_colors(grid));
+        let blend = if style.blend > 0 {
+            style.blend
+        } else {
+            self.grid_blend(grid)
+        };
+
+        if grid == MAIN_GRID || blend == 0 {
+            return background;
+        }
+
+        let (grid_x, grid_y) = self.grid_position(grid);
+        let underlying = self
+            .cell_style(MAIN_GRID, grid_x + x, grid_y + y)
+            .background(default_colors);
+
+        blend_color(&background, &underlying, blend)
+    }
+
+    // The background of every main grid cell by row, for downscaling into a thumbnail
+    #[allow(dead_code)]
+    pub fn background_thumbnail(&self) -> Vec<Vec<Color4f>> {
+        let default_colors = &self.default_style.colors;
+        (0..self.grid.height)
+            .map(|y| {
+                (0..self.grid.width)
+                    .map(|x| self.cell_style(MAIN_GRID, x, y).background(default_colors))
+                    .collect()
+            })
+            .collect()
+    }
+
+    // The hyperlink attached to a main grid cell through its highlight
+    #[allow(dead_code)]
+    pub fn url_at(&self, row: u64, col: u64) -> Option<&str> {
+        match self.grid.get_cell(col, row) {
+            Some(Some((_, Some(style)))) => style.url.as_deref(),
+            _ => None,
+        }
+    }
+
+    // The url to open for a click on a main grid cell, if the cell is part of a hyperlink
+    #[allow(dead_code)]
+    pub fn handle_url_click(&mut self, row: u64, col: u64) -> Option<String> {
+        let url = self.url_at(row, col)?.to_string();
+        trace!("Hyperlink clicked {}", &url);
+        Some(url)
+    }
+
+    // Breaks externally drawn text such as messages into lines at most `width` cells wide.
+    // Lines are only broken between graphemes, and a wide character never straddles two lines
+    #[allow(dead_code)]
+    pub fn wrap_text(&self, text: &str, width: u64) -> Vec<String> {
+        let mut lines = Vec::new();
+        for text_line in text.lines() {
+            let mut line = String::new();
+            let mut line_width = 0;
+            for grapheme in text_line.graphemes(true) {
+                let grapheme_width = display_width(grapheme);
+                if line_width + grapheme_width > width && !line.is_empty() {
+                    lines.push(std::mem::take(&mut line));
+                    line_width = 0;
+                }
+                line.push_str(grapheme);
+                line_width += grapheme_width;
+            }
+            lines.push(line);
+        }
+        lines
+    }
+
+    // Returns how many times in a row this button has been clicked on this cell, up to a
+    // triple click after which the count starts over
+    pub fn mouse_press_timed(
+        &mut self,
+        row: u64,
+        col: u64,
+        button: &str,
+        timestamp_ms: u64,
+    ) -> u64 {
+        let repeated = match &self.last_mouse_press {
+            Some((position, last_button, last_timestamp)) => {
+                *position == (col, row)
+                    && last_button == button
+                    && timestamp_ms.saturating_sub(*last_timestamp) <= MULTI_CLICK_TIMEOUT_MS
+            }
+            None => false,
+        };
+
+        self.click_count = if repeated && self.click_count < 3 {
+            self.click_count + 1
+        } else {
+            1
+        };
+        self.last_mouse_press = Some(((col, row), button.to_string(), timestamp_ms));
+
+        trace!("Mouse {} pressed {} times", button, self.click_count);
+        self.click_count
+    }
+
+    // Returns whether a mouse move to this cell should be forwarded to Neovim. Moves are only
+    // sent while mousemoveevent is set, and only once per cell
+    pub fn mouse_move(&mut self, row: u64, col: u64) -> bool {
+        if !self.mouse_move_enabled || self.last_mouse_move == Some((col, row)) {
+            return false;
+        }
+
+        self.last_mouse_move = Some((col, row));
+        trace!("Mouse moved to {} {}", col, row);
+        true
+    }
+
+    // Pairs each chunk of a paste with its nvim_paste phase, so Neovim treats the chunks as one
+    // paste and one undo step. A lone chunk is sent with phase -1, otherwise the phases run
+    // 1, 2, ..., 2, 3
+    pub fn paste_stream(&self, chunks: impl Iterator<Item = String>) -> Vec<(String, i64)> {
+        let chunks: Vec<String> = chunks.collect();
+        let last_index = chunks.len().saturating_sub(1);
+        trace!("Paste of {} chunks", chunks.len());
+        chunks
+            .into_iter()
+            .enumerate()
+            .map(|(index, data)| {
+                let phase = match index {
+                    _ if last_index == 0 => -1,
+                    0 => 1,
+                    _ if index == last_index => 3,
+                    _ => 2,
+                };
+                (data, phase)
+            })
+            .collect()
+    }
+
+    // Forces every cell of every grid to be repainted on the next frame
+    pub fn mark_all_dirty(&mut self) {
+        trace!("All grids marked dirty");
+        self.grid.set_dirty_all(true);
+        for grid in self.grids.values_mut() {
+            grid.set_dirty_all(true);
+        }
+    }
+
+    // Painting is skipped while suspended, so everything is redrawn on resume
+    pub fn set_suspended(&mut self, suspended: bool) {
+        trace!("Suspended {}", suspended);
+        if self.suspended && !suspended {
+            self.mark_all_dirty();
+        }
+        self.suspended = suspended;
+    }
+
+    // Cells drawn with a removed id afterwards fall back to the default style
+    #[allow(dead_code)]
+    pub fn undefine_style(&mut self, id: u64) {
+        trace!("Highlight {} removed", id);
+        self.resolved_style = None;
+        self.defined_styles.remove(&id);
+    }
+
+    // A readable listing of the default colors and every defined highlight, for bug reports
+    #[allow(dead_code)]
+    pub fn dump_styles(&self) -> String {
+        fn hex(color: &Option<Color4f>) -> String {
+            match color {
+                Some(color) => format!(
+                    "#{:02x}{:02x}{:02x}",
+                    (color.r * 255.0).round() as u8,
+                    (color.g * 255.0).round() as u8,
+                    (color.b * 255.0).round() as u8
+                ),
+                None => "none".to_string(),
+            }
+        }
+
+        fn describe(style: &Style) -> String {
+            let flags: Vec<&str> = [
+                (style.reverse, "reverse"),
+                (style.italic, "italic"),
+                (style.bold, "bold"),
+                (style.strikethrough, "strikethrough"),
+                (style.underline, "underline"),
+                (style.undercurl, "undercurl"),
+            ]
+            .iter()
+            .filter(|(enabled, _)| *enabled)
+            .map(|(_, name)| *name)
+            .collect();
+
+            format!(
+                "fg={} bg={} sp={} blend={} flags=[{}]",
+                hex(&style.colors.foreground),
+                hex(&style.colors.background),
+                hex(&style.colors.special),
+                style.blend,
+                flags.join(",")
+            )
+        }
+
+        let mut ids: Vec<&u64> = self.defined_styles.keys().collect();
+        ids.sort();
+
+        let mut dump = format!("default {}\n", describe(&self.default_style));
+        for id in ids {
+            dump.push_str(&format!("{} {}\n", id, describe(&self.defined_styles[id])));
+        }
+        dump
+    }
+
+    fn set_default_colors(&mut self, colors: Colors) {
+        if colors.background != self.default_style.colors.background {
+            trace!("Default background changed");
+            self.background_changed = true;
+            self.mark_all_dirty();
+        }
+        self.default_style = Arc::new(Style::new(colors));
+    }
+
+    // Every cell is repainted with the new filter, including the cleared background
+    #[allow(dead_code)]
+    pub fn set_color_filter(&mut self, filter: ColorFilter) {
+        if filter != self.color_filter {
+            trace!("Color filter set to {:?}", &filter);
+            self.color_filter = filter;
+            self.mark_all_dirty();
+            self.grid.should_clear = true;
+        }
+    }
+
+    // Limits rendered colors to a few levels per channel for low color displays
+    #[allow(dead_code)]
+    pub fn set_color_levels(&mut self, levels: Option<u8>) {
+        if levels != self.color_levels {
+            trace!("Color levels set to {:?}", levels);
+            self.color_levels = levels;
+            self.mark_all_dirty();
+            self.grid.should_clear = true;
+        }
+    }
+
+    // The cursor shape with the foreground and background to draw it in. A mode's cursor
+    // highlight wins, otherwise the cell under the cursor is drawn inverted
+    #[allow(dead_code)]
+    pub fn cursor_render(&self) -> (CursorShape, Color4f, Color4f) {
+        let default_colors = &self.default_style.colors;
+        let (foreground, background) = match &self.cursor.style {
+            Some(_) => (
+                self.cursor.foreground(default_colors),
+                self.cursor.background(default_colors),
+            ),
+            None => {
+                let (x, y) = self.cursor.position;
+                let style = self.cell_style(self.focused_grid, x, y);
+                (
+                    style.background(default_colors),
+                    style.foreground(default_colors),
+                )
+            }
+        };
+        (self.cursor.shape.clone(), foreground, background)
+    }
+
+    // How many columns a block cursor covers, which is two on either half of a wide character
+    #[allow(dead_code)]
+    pub fn cursor_cell_span(&self) -> u64 {
+        let (_, span) = self.character_cells(self.focused_grid, self.cursor.position);
+        span
+    }
+
+    // The first column and width of the character covering a cell, so either half of a wide
+    // character gives the whole of it
+    fn character_cells(&self, grid: u64, (x, y): (u64, u64)) -> (u64, u64) {
+        let grid = match self.get_grid(grid) {
+            Some(grid) => grid,
+            None => return (x, 1),
+        };
+        let is_placeholder = |x: u64| match grid.get_cell(x, y) {
+            Some(Some((text, _))) => text.is_empty(),
+            _ => false,
+        };
+
+        if is_placeholder(x + 1) {
+            (x, 2)
+        } else if x > 0 && is_placeholder(x) {
+            (x - 1, 2)
+        } else {
+            (x, 1)
+        }
+    }
+
+    // Cells as (x, y) in the previous cursor grid that the cursor covered before its last move
+    #[allow(dead_code)]
+    pub fn previous_cursor_cells(&self) -> Vec<(u64, u64)> {
+        match self.previous_cursor_position {
+            Some((grid, (x, y))) => {
+                let (start, span) = self.character_cells(grid, (x, y));
+                (start..start + span).map(|x| (x, y)).collect()
+            }
+            None => Vec::new(),
+        }
+    }
+
+    // Asks the cursor renderer to jump straight to the cursor instead of gliding there
+    pub fn snap_cursor(&mut self) {
+        trace!("Cursor snap requested");
+        self.cursor_snap_requested = true;
+    }
+
+    // Consumed by the cursor renderer once per frame. When it returns true the renderer moves
+    // its corners straight onto the cursor that frame and skips the command line delay
+    pub fn take_cursor_snap(&mut self) -> bool {
+        std::mem::replace(&mut self.cursor_snap_requested, false)
+    }
+
+    // Whether the window background needs clearing since the last call
+    pub fn take_background_changed(&mut self) -> bool {
+        std::mem::replace(&mut self.background_changed, false)
+    }
+
+    // Applies the response of nvim_get_mode, queried when the mode may be stale. The cursor is
+    // hidden while Neovim is blocked waiting for input
+    pub fn refresh_mode(&mut self, mode_status: Vec<(Value, Value)>) {
+        match parse_mode_status(mode_status) {
+            Ok((mode, blocking)) => {
+                trace!("Mode refreshed {:?}, blocking {}", &mode, blocking);
+                self.current_mode = mode;
+                self.cursor.enabled = !blocking;
+            }
+            Err(error) => error!("Could not parse mode: {}", error),
+        }
+    }
+
+    pub fn set_hl_group(&mut self, name: String, id: u64) {
+        trace!("Highlight group {} set to {}", &name, id);
+        self.hl_groups.insert(name, id);
+    }
+
+    #[allow(dead_code)]
+    pub fn style_for_group(&self, name: &str) -> Option<&Style> {
+        self.hl_groups
+            .get(name)
+            .and_then(|id| self.defined_styles.get(id))
+            .map(|style| style.as_ref())
+    }
+
+    fn grid_draw_commands(&self) -> Vec<DrawCommand> {
+        let mut draw_commands = Vec::new();
+
+        for (row_index, row) in self.grid.rows().enumerate() {
+            let mut command = None;
+
+            fn add_command(commands_list: &mut Vec<DrawCommand>, command: Option<DrawCommand>) {
+                if let Some(command) = command {
+                    commands_list.push(command);
+                }
+            }
+
+            fn command_matches(command: &Option<DrawCommand>, style: &Option<Arc<Style>>) -> bool {
+                match command {
+                    Some(command) => &command.style == style,
+                    None => true,
+                }
+            }
+
+            // The style is only cloned when a run starts, not for every character in it
+            fn add_character(
+                command: &mut Option<DrawCommand>,
+                character: &str,
+                row_index: u64,
+                col_index: u64,
+                style: &Option<Arc<Style>>,
+            ) {
+                match command {
+                    Some(command) => {
+                        command.text.push_str(character);
+                        command.cell_width += 1;
+                    }
+                    None => {
+                        command.replace(DrawCommand::new(
+                            character.to_string(),
+                            1,
+                            (col_index, row_index),
+                            style.clone(),
+                        ));
+                    }
+                }
+            }
+
+            // Empty cells between drawn ones take the row's main style so solid backgrounds
+            // don't get gaps
+            let (content_width, dominant_style) = if self.fill_row_gaps {
+                row_content(row)
+            } else {
+                (0, None)
+            };
+
+            for (col_index, cell) in row.iter().enumerate() {
+                let filled_cell;
+                let cell = if cell.is_none() && col_index < content_width {
+                    filled_cell = Some((" ".to_string(), dominant_style.clone()));
+                    &filled_cell
+                } else {
+                    cell
+                };
+
+                if let Some((character, style)) = cell {
+                    if character.is_empty() {
+                        add_character(
+                            &mut command,
+                            &" ",
+                            row_index as u64,
+                            col_index as u64,
+                            style,
+                        );
+                        add_command(&mut draw_commands, command);
+                        command = None;
+                    } else {
+                        // Without ligatures every character is shaped on its own
+                        if !self.ligatures_enabled || !command_matches(&command, &style) {
+                            add_command(&mut draw_commands, command);
+                            command = None;
+                        }
+                        add_character(
+                            &mut command,
+                            &character,
+                            row_index as u64,
+                            col_index as u64,
+                            style,
+                        );
+                    }
+                } else {
+                    if !command_matches(&command, &None) {
+                        add_command(&mut draw_commands, command);
+                        command = None;
+                    }
+                    add_character(&mut command, " ", row_index as u64, col_index as u64, &None);
+                }
+            }
+            add_command(&mut draw_commands, command);
+        }
+
+        draw_commands
+    }
+
+    #[allow(dead_code)]
+    pub fn set_ligatures_enabled(&mut self, on: bool) {
+        if self.ligatures_enabled != on {
+            trace!("Ligatures enabled {}", on);
+            self.ligatures_enabled = on;
+            self.mark_all_dirty();
+        }
+    }
+
+    #[allow(dead_code)]
+    pub fn ligatures_enabled(&self) -> bool {
+        self.ligatures_enabled
+    }
+
+    // Called on every flush. Returns how many rows the main grid content moved up since the
+    // previous flush, or None if the change doesn't look like a scroll
+    pub fn snapshot_and_detect_scroll(&mut self) -> Option<isize> {
+        let snapshot: Vec<Vec<GridCell>> = self.grid.rows().map(|row| row.to_vec()).collect();
+        let previous = std::mem::replace(&mut self.previous_snapshot, snapshot);
+        let current = &self.previous_snapshot;
+
+        if previous.len() != current.len() || previous == *current {
+            return None;
+        }
+
+        let height = current.len() as isize;
+        let is_blank = |row: &Vec<GridCell>| row.iter().all(|cell| cell.is_none());
+
+        for distance in 1..=(height / 2) {
+            for &shift in &[distance, -distance] {
+                let overlap = (0..height).filter(|y| (0..height).contains(&(y + shift)));
+                let mut matched = false;
+                let mut all_match = true;
+
+                for y in overlap {
+                    let current_row = &current[y as usize];
+                    if *current_row != previous[(y + shift) as usize] {
+                        all_match = false;
+                        break;
+                    }
+                    matched |= !is_blank(current_row);
+                }
+
+                if all_match && matched {
+                    trace!("Scroll of {} rows detected", shift);
+                    self.last_scroll = Some(shift as i64);
+                    return Some(shift);
+                }
+            }
+        }
+
+        None
+    }
+
+    fn flush(&mut self) {
+        trace!("Image flushed");
+        self.pending_clear = false;
+        self.snapshot_and_detect_scroll();
+    }
+
+    // Commands are ordered top to bottom and left to right within each row, which the
+    // renderer relies on when painting overlapping glyphs. Nothing is returned between a clear
+    // and the next flush so the blank grid is never shown on its own.
+    pub fn build_draw_commands(&mut self) -> (Vec<DrawCommand>, bool) {
+        if self.pending_clear {
+            return (Vec::new(), false);
+        }
+
+        let should_clear = self.grid.should_clear;
+        let draw_commands = self
+            .grid_draw_commands()
+            .into_iter()
+            .filter(|command| {
+                let (x, y) = command.grid_position;
+                let min = (x as i64 - 1).max(0) as u64;
+                let max = (x + command.cell_width + 1).min(self.grid.width);
+
+                for char_index in min..max {
+                    if self.grid.is_dirty_cell(char_index, y) {
+                        return true;
+                    }
+                }
+                false
+            })
+            .collect::<Vec<DrawCommand>>();
+
+        self.grid.set_dirty_all(false);
+        self.grid.should_clear = false;
+
+        trace!("Draw commands sent");
+        (draw_commands, should_clear)
+    }
+
+    #[allow(dead_code)]
+    pub fn set_debug_overlay(&mut self, on: bool) {
+        self.debug_overlay = on;
+    }
+
+    // Runs of dirty main grid cells as (x, y, width, height) so the renderer can tint what it
+    // is about to redraw. Must be read before the draw commands are built, which clears them
+    pub fn debug_overlay_rects(&self) -> Vec<(u64, u64, u64, u64)> {
+        let mut rects = Vec::new();
+        if !self.debug_overlay {
+            return rects;
+        }
+
+        for y in 0..self.grid.height {
+            let mut start = None;
+            for x in 0..=self.grid.width {
+                match (start, x < self.grid.width && self.grid.is_dirty_cell(x, y)) {
+                    (None, true) => start = Some(x),
+                    (Some(start_x), false) => {
+                        rects.push((start_x, y, x - start_x, 1));
+                        start = None;
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        rects
+    }
+
+    // Runs of main grid rows holding a dirty cell as (start_row, end_row), with the end exclusive
+    #[allow(dead_code)]
+    pub fn dirty_spans(&self) -> Vec<(u64, u64)> {
+        let mut spans: Vec<(u64, u64)> = Vec::new();
+        for y in 0..self.grid.height {
+            if !(0..self.grid.width).any(|x| self.grid.is_dirty_cell(x, y)) {
+                continue;
+            }
+            match spans.last_mut() {
+                Some((_, end)) if *end == y => *end = y + 1,
+                _ => spans.push((y, y + 1)),
+            }
+        }
+        spans
+    }
+
+    // How many runs the whole main grid breaks into, dirty or not. Splits runs the same way
+    // grid_draw_commands does, but only counts them
+    #[allow(dead_code)]
+    pub fn draw_command_count(&self) -> usize {
+        let no_style = None;
+        let mut count = 0;
+
+        for row in self.grid.rows() {
+            let (content_width, dominant_style) = if self.fill_row_gaps {
+                row_content(row)
+            } else {
+                (0, None)
+            };
+
+            // The style of the run being built, if there is one
+            let mut run_style: Option<&Option<Arc<Style>>> = None;
+
+            for (col_index, cell) in row.iter().enumerate() {
+                let cell = match cell {
+                    Some((character, style)) => Some((character.as_str(), style)),
+                    None if col_index < content_width => Some((" ", &dominant_style)),
+                    None => None,
+                };
+
+                match cell {
+                    // The second half of a double width character ends its run
+                    Some(("", _)) => {
+                        count += 1;
+                        run_style = None;
+                    }
+                    Some((_, style)) => {
+                        let breaks = !self.ligatures_enabled
+                            || matches!(run_style, Some(run_style) if run_style != style);
+                        if breaks && run_style.take().is_some() {
+                            count += 1;
+                        }
+                        run_style.get_or_insert(style);
+                    }
+                    None => {
+                        if matches!(run_style, Some(Some(_))) {
+                            count += 1;
+                            run_style = None;
+                        }
+                        run_style.get_or_insert(&no_style);
+                    }
+                }
+            }
+
+            if run_style.is_some() {
+                count += 1;
+            }
+        }
+
+        count
+    }
+
+    // Every run in the main grid keyed by its style, with unstyled runs under the default style
+    #[allow(dead_code)]
+    pub fn draw_commands_grouped_by_style(&self) -> HashMap<Style, Vec<DrawCommand>> {
+        let mut groups: HashMap<Style, Vec<DrawCommand>> = HashMap::new();
+
+        for command in self.grid_draw_commands() {
+            let style = command
+                .style
+                .as_ref()
+                .unwrap_or(&self.default_style)
+                .as_ref()
+                .clone();
+            groups.entry(style).or_default().push(command);
+        }
+
+        groups
+    }
+
+    // Remembers the last highlight id looked up, since cells in a batch often repeat it
+    fn resolve_style(&mut self, style_id: u64) -> Option<Arc<Style>> {
+        match &self.resolved_style {
+            Some((resolved_id, style)) if *resolved_id == style_id => style.clone(),
+            _ => {
+                self.style_lookups += 1;
+                let style = self.defined_styles.get(&style_id).cloned();
+                self.resolved_style = Some((style_id, style.clone()));
+                style
+            }
+        }
+    }
+
+    fn draw_grid_line_cell(
+        &mut self,
+        grid: u64,
+        row_index: u64,
+        column_pos: &mut u64,
+        cell: GridLineCell,
+    ) {
+        let style = match cell.highlight_id {
+            Some(0) => None,
+            Some(style_id) => self.resolve_style(style_id),
+            None => self.previous_style.clone(),
+        };
+        // Cells which write nothing only change the inherited style if they name one
+        let updates_previous_style = cell.highlight_id.is_some() || !cell.text.is_empty();
+
+        if let Some(grid) = self.get_grid_mut(grid) {
+            if cell.text.is_empty() {
+                if let Some(cell) = grid.get_cell_mut(*column_pos, row_index) {
+                    *cell = Some(("".to_string(), style.clone()));
+                } else if *column_pos == grid.width && *column_pos > 0 {
+                    // A wide character in the last column has no room for its second half, so
+                    // leave the column blank instead of drawing a clipped glyph
+                    if let Some(cell) = grid.get_cell_mut(*column_pos - 1, row_index) {
+                        *cell = None;
+                    }
+                    grid.set_dirty_cell(*column_pos - 1, row_index);
+                }
+
+                grid.set_dirty_cell(*column_pos, row_index);
+                *column_pos += 1;
+            } else if let (None, " ", Some(times)) = (&style, cell.text.as_str(), cell.repeat) {
+                // Cleared runs are stored as empty cells to keep the grid sparse
+                if let Some(cells) = grid.row_span_mut(*column_pos, row_index, times as usize) {
+                    for cell in cells.iter_mut() {
+                        *cell = None;
+                    }
+                }
+                *column_pos += times;
+            } else {
+                let text = match cell.repeat {
+                    Some(times) => cell.text.repeat(times as usize),
+                    None => cell.text,
+                };
+                let mut characters: Vec<&str> = text.graphemes(true).collect();
+
+                // A combining mark at the start of a cell belongs to the character before it
+                let previous_column = column_pos.checked_sub(1);
+                if let (Some(first), Some(previous_column)) = (characters.first(), previous_column)
+                {
+                    if starts_with_combining_mark(first) {
+                        if let Some(Some((previous, _))) =
+                            grid.get_cell_mut(previous_column, row_index)
+                        {
+                            if !previous.is_empty() {
+                                previous.push_str(first);
+                                characters.remove(0);
+                                grid.set_dirty_cell(previous_column, row_index);
+                            }
+                        }
+                    }
+                }
+
+                if let Some(cells) = grid.row_span_mut(*column_pos, row_index, characters.len()) {
+                    for (cell, character) in cells.iter_mut().zip(&characters) {
+                        *cell = Some((cell_text(character), style.clone()));
+                    }
+                }
+                *column_pos += characters.len() as u64;
+            }
+        }
+
+        if updates_previous_style {
+            self.previous_style = style;
+        }
+    }
+
+    // Returns whether the line can be drawn
+    fn begin_grid_line(&mut self, grid: u64, row: u64, column_start: u64) -> bool {
+        let in_bounds = self
+            .get_grid(grid)
+            .map(|grid| row < grid.height && column_start < grid.width)
+            .unwrap_or(false);
+
+        if in_bounds {
+            // Omitted highlight ids only refer back to earlier cells in the same line
+            self.previous_style = None;
+        } else {
+            self.out_of_bounds_lines += 1;
+            error!(
+                "Draw command out of bounds: grid {} row {} column {}",
+                grid, row, column_start
+            );
+        }
+        in_bounds
+    }
+
+    fn draw_grid_line(&mut self, grid: u64, row: u64, column_start: u64, cells: Vec<GridLineCell>) {
+        if self.begin_grid_line(grid, row, column_start) {
+            let mut column_pos = column_start;
+            for cell in cells {
+                self.draw_grid_line_cell(grid, row, &mut column_pos, cell);
+            }
+        }
+    }
+
+    // Draws the decoded lines of a grid_line event in order as (grid, row, column_start, cells)
+    pub fn apply_grid_lines(&mut self, lines: Vec<(u64, u64, u64, Vec<GridLineCell>)>) {
+        self.resolved_style = None;
+        for (grid, row, column_start, cells) in lines {
+            self.draw_grid_line(grid, row, column_start, cells);
+        }
+    }
+
+    // Like apply_grid_lines, but stops after drawing `chunk_size` cells so the caller can render
+    // in between. The rest of the batch is kept and picked up by the next call, which may add
+    // more lines behind it. Returns true once everything queued has been drawn
+    #[allow(dead_code)]
+    pub fn apply_grid_lines_chunked(
+        &mut self,
+        lines: Vec<(u64, u64, u64, Vec<GridLineCell>)>,
+        chunk_size: usize,
+    ) -> bool {
+        self.resolved_style = None;
+        self.pending_grid_lines.extend(
+            lines
+                .into_iter()
+                .map(|(grid, row, column_start, cells)| (grid, row, column_start, cells.into())),
+        );
+
+        let mut drawn_cells = 0;
+        while drawn_cells < chunk_size.max(1) {
+            let (grid, row, column_start) = match self.pending_grid_lines.front() {
+                Some((grid, row, column_start, _)) => (*grid, *row, *column_start),
+                None => break,
+            };
+
+            let mut column_pos = match self.pending_line_column.take() {
+                Some(column_pos) => column_pos,
+                None if self.begin_grid_line(grid, row, column_start) => column_start,
+                None => {
+                    self.pending_grid_lines.pop_front();
+                    continue;
+                }
+            };
+
+            let cell = self
+                .pending_grid_lines
+                .front_mut()
+                .and_then(|(_, _, _, cells)| cells.pop_front());
+            match cell {
+                Some(cell) => {
+                    self.draw_grid_line_cell(grid, row, &mut column_pos, cell);
+                    self.pending_line_column = Some(column_pos);
+                    drawn_cells += 1;
+                }
+                None => {
+                    self.pending_grid_lines.pop_front();
+                }
+            }
+        }
+
+        // A line whose last cell was just drawn is finished, not waiting
+        if let Some((_, _, _, cells)) = self.pending_grid_lines.front() {
+            if cells.is_empty() {
+                self.pending_grid_lines.pop_front();
+                self.pending_line_column = None;
+            }
+        }
+
+        trace!("{} grid lines still pending", self.pending_grid_lines.len());
+        self.pending_grid_lines.is_empty()
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn scroll_region(
+        &mut self,
+        grid: u64,
+        top: u64,
+        bot: u64,
+        left: u64,
+        right: u64,
+        rows: i64,
+        cols: i64,
+    ) {
+        let grid = match self.get_grid_mut(grid) {
+            Some(grid) => grid,
+            None => return,
+        };
+
+        let y_iter: Box<dyn Iterator<Item = i64>> = if rows > 0 {
+            Box::new((top as i64 + rows)..bot as i64)
+        } else {
+            Box::new((top as i64..(bot as i64 + rows)).rev())
+        };
+
+        for y in y_iter {
+            let dest_y = y - rows;
+            if dest_y >= 0 && dest_y < grid.height as i64 {
+                let x_iter: Box<dyn Iterator<Item = i64>> = if cols > 0 {
+                    Box::new((left as i64 + cols)..right as i64)
+                } else {
+                    Box::new((left as i64..(right as i64 + cols)).rev())
+                };
+
+                for x in x_iter {
+                    let dest_x = x - cols;
+                    let cell_data = grid.get_cell(x as u64, y as u64).cloned();
+
+                    if let Some(cell_data) = cell_data {
+                        if let Some(dest_cell) = grid.get_cell_mut(dest_x as u64, dest_y as u64) {
+                            *dest_cell = cell_data;
+                            grid.set_dirty_cell(dest_x as u64, dest_y as u64);
+                        }
+                    }
+                }
+            }
+        }
+        trace!("Region scrolled");
+    }
+
+    // Bumped every time the cell changes so renderers can cache per cell
+    #[allow(dead_code)]
+    pub fn cell_version(&self, row: u64, col: u64) -> u64 {
+        self.grid.cell_version(col, row)
+    }
+
+    // Whether a main grid row would draw nothing but the default background
+    #[allow(dead_code)]
+    pub fn row_is_blank(&self, row: u64) -> bool {
+        let cells = match self.grid.rows().nth(row as usize) {
+            Some(cells) => cells,
+            None => return false,
+        };
+
+        cells.iter().all(|cell| match cell {
+            None => true,
+            Some((text, style)) => {
+                text == " "
+                    && style
+                        .as_ref()
+                        .map(|style| style.as_ref() == self.default_style.as_ref())
+                        .unwrap_or(true)
+            }
+        })
+    }
+
+    // One past the last main grid row with any filled cell, or 0 for a blank grid
+    #[allow(dead_code)]
+    pub fn content_rows(&self) -> u64 {
+        self.grid
+            .rows()
+            .enumerate()
+            .filter(|(_, cells)| cells.iter().any(|cell| cell.is_some()))
+            .last()
+            .map(|(row, _)| row as u64 + 1)
+            .unwrap_or(0)
+    }
+
+    // Whether a main grid row still matches the same row of an earlier snapshot. Slice
+    // equality checks the lengths first and stops at the first differing cell
+    #[allow(dead_code)]
+    pub fn row_unchanged_since(&self, row: u64, snapshot: &[Vec<GridCell>]) -> bool {
+        match (
+            self.grid.rows().nth(row as usize),
+            snapshot.get(row as usize),
+        ) {
+            (Some(cells), Some(snapshot_cells)) => cells == snapshot_cells.as_slice(),
+            _ => false,
+        }
+    }
+
+    // The character and style of a main grid row made entirely of one filled cell, such as a
+    // separator line
+    #[allow(dead_code)]
+    pub fn row_is_uniform(&self, row: u64) -> Option<(char, Style)> {
+        let cells = self.grid.rows().nth(row as usize)?;
+        let first = cells.first()?.as_ref()?;
+        if !cells.iter().all(|cell| cell.as_ref() == Some(first)) {
+            return None;
+        }
+
+        let (text, style) = first;
+        let mut characters = text.chars();
+        match (characters.next(), characters.next()) {
+            (Some(character), None) => Some((
+                character,
+                style
+                    .as_ref()
+                    .unwrap_or(&self.default_style)
+                    .as_ref()
+                    .clone(),
+            )),
+            _ => None,
+        }
+    }
+
+    // Every main grid cell as a character with its resolved foreground and background, for
+    // screen readers and other tools which can't read styles
+    #[allow(dead_code)]
+    pub fn accessibility_snapshot(&self) -> Vec<Vec<(char, Color4f, Color4f)>> {
+        let default_colors = &self.default_style.colors;
+        (0..self.grid.height)
+            .map(|y| {
+                (0..self.grid.width)
+                    .map(|x| {
+                        let character = match self.grid.get_cell(x, y) {
+                            Some(Some((text, _))) => text.chars().next().unwrap_or(' '),
+                            _ => ' ',
+                        };
+                        let style = self.cell_style(MAIN_GRID, x, y);
+                        (
+                            character,
+                            style.foreground(default_colors),
+                            style.background(default_colors),
+                        )
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    // The columns [start, end) of the word under the cursor on the main grid, where words are
+    // runs of alphanumeric characters and underscores
+    #[allow(dead_code)]
+    pub fn word_at_cursor(&self) -> Option<(u64, u64)> {
+        let (x, y) = self.cursor.position;
+        let is_word_cell = |x: u64| match self.grid.get_cell(x, y) {
+            Some(Some((text, _))) => text
+                .chars()
+                .next()
+                .map(|character| character.is_alphanumeric() || character == '_')
+                .unwrap_or(false),
+            _ => false,
+        };
+
+        if !is_word_cell(x) {
+            return None;
+        }
+
+        let start = (0..x)
+            .rev()
+            .find(|x| !is_word_cell(*x))
+            .map(|x| x + 1)
+            .unwrap_or(0);
+        let end = (x + 1..self.grid.width)
+            .find(|x| !is_word_cell(*x))
+            .unwrap_or(self.grid.width);
+        Some((start, end))
+    }
+
+    // The text of the main grid row holding the cursor, with empty cells read as spaces
+    #[allow(dead_code)]
+    pub fn cursor_line_text(&self) -> String {
+        let (_, row) = self.cursor.position;
+        let text: String = self
+            .grid
+            .rows()
+            .nth(row as usize)
+            .map(|cells| {
+                cells
+                    .iter()
+                    .map(|cell| match cell {
+                        Some((text, _)) => text.as_str(),
+                        None => " ",
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+        text.trim_end().to_string()
+    }
+
+    // Coordinates (x, y) of main grid cells whose text or style differ from another editor's.
+    // Grids of different sizes differ everywhere
+    #[allow(dead_code)]
+    pub fn diff_grids(&self, other: &Editor) -> Vec<(u64, u64)> {
+        let same_size =
+            self.grid.width == other.grid.width && self.grid.height == other.grid.height;
+        let mut differences = Vec::new();
+        for y in 0..self.grid.height {
+            for x in 0..self.grid.width {
+                if !same_size || self.grid.get_cell(x, y) != other.grid.get_cell(x, y) {
+                    differences.push((x, y));
+                }
+            }
+        }
+        differences
+    }
+
+    // Copies the cells in (left, top, right, bottom) to the same place in another grid, only
+    // touching cells which exist in both
+    #[allow(dead_code)]
+    pub fn copy_grid_region(
+        &mut self,
+        source_grid: u64,
+        destination_grid: u64,
+        region: (u64, u64, u64, u64),
+    ) {
+        let (left, top, right, bottom) = region;
+        let (right, bottom) = match (self.get_grid(source_grid), self.get_grid(destination_grid)) {
+            (Some(source), Some(destination)) => (
+                right.min(source.width).min(destination.width),
+                bottom.min(source.height).min(destination.height),
+            ),
+            _ => return,
+        };
+
+        let mut cells = Vec::new();
+        if let Some(source) = self.get_grid(source_grid) {
+            for y in top..bottom {
+                for x in left..right {
+                    cells.push(((x, y), source.get_cell(x, y).cloned().flatten()));
+                }
+            }
+        }
+
+        if let Some(destination) = self.get_grid_mut(destination_grid) {
+            for ((x, y), cell) in cells {
+                if let Some(destination_cell) = destination.get_cell_mut(x, y) {
+                    *destination_cell = cell;
+                    destination.set_dirty_cell(x, y);
+                }
+            }
+        }
+        trace!("Region copied from {} to {}", source_grid, destination_grid);
+    }
+
+    pub fn show_wildmenu(&mut self, items: Vec<String>) {
+        trace!("Wildmenu shown with {} items", items.len());
+        self.wildmenu_items = items;
+        self.wildmenu_selected = None;
+    }
+
+    // Neovim sends -1 when no item is selected. Indices past the end are ignored
+    pub fn select_wildmenu(&mut self, selected: i64) {
+        if selected < 0 {
+            self.wildmenu_selected = None;
+        } else if (selected as usize) < self.wildmenu_items.len() {
+            self.wildmenu_selected = Some(selected as usize);
+        }
+    }
+
+    pub fn hide_wildmenu(&mut self) {
+        trace!("Wildmenu hidden");
+        self.wildmenu_items.clear();
+        self.wildmenu_selected = None;
+    }
+
+    // Keeps a msg_show for the history. The message takes the style of its first chunk and
+    // replaces the previous one when Neovim asks for that
+    fn record_message(&mut self, content: StyledContent, replace_last: bool) {
+        let style = content
+            .first()
+            .and_then(|(style_id, _)| self.defined_styles.get(style_id))
+            .unwrap_or(&self.default_style)
+            .as_ref()
+            .clone();
+        let text: String = content.into_iter().map(|(_, text)| text).collect();
+
+        if replace_last {
+            self.message_history.pop_back();
+        }
+        self.message_history.push_back((style, text));
+        while self.message_history.len() > self.message_history_capacity {
+            self.message_history.pop_front();
+        }
+    }
+
+    // Up to `limit` of the most recent messages, oldest first
+    #[allow(dead_code)]
+    pub fn message_history(&self, limit: usize) -> Vec<(Style, String)> {
+        let skipped = self.message_history.len().saturating_sub(limit);
+        self.message_history.iter().skip(skipped).cloned().collect()
+    }
+
+    #[allow(dead_code)]
+    pub fn set_message_history_capacity(&mut self, capacity: usize) {
+        self.message_history_capacity = capacity;
+        while self.message_history.len() > capacity {
+            self.message_history.pop_front();
+        }
+    }
+
+    // Families tried in order when the guifont lacks a glyph. Empty means the system default
+    #[allow(dead_code)]
+    pub fn set_font_fallbacks(&mut self, families: Vec<String>) {
+        trace!("Font fallbacks set {:?}", &families);
+        self.font_fallbacks = families;
+    }
+
+    #[allow(dead_code)]
+    pub fn font_fallbacks(&self) -> &[String] {
+        &self.font_fallbacks
+    }
+
+    // The family and size set by guifontwide, for drawing double width characters
+    #[allow(dead_code)]
+    pub fn guifont_wide(&self) -> Option<(String, Option<f32>)> {
+        self.guifont_wide
+            .as_ref()
+            .and_then(|setting| parse_font_setting(setting))
+    }
+
+    // The title is compared against the window's every frame, so a title built from the working
+    // directory picks up the change without a separate flag
+    pub fn set_working_dir(&mut self, dir: String) {
+        trace!("Working directory changed to {}", &dir);
+        self.working_dir = Some(dir);
+    }
+
+    #[allow(dead_code)]
+    pub fn working_dir(&self) -> Option<&str> {
+        self.working_dir.as_deref()
+    }
+
+    fn set_option(&mut self, gui_option: GuiOption) {
+        trace!("Option set {:?}", &gui_option);
+        match gui_option {
+            GuiOption::GuiFont(guifont) => self.guifont = Some(guifont),
+            GuiOption::GuiFontWide(guifont_wide) => self.guifont_wide = Some(guifont_wide),
+            GuiOption::LineSpace(linespace) => self.linespace = linespace,
+            GuiOption::MouseMoveEvent(enabled) => {
+                self.mouse_move_enabled = enabled;
+                self.last_mouse_move = None;
+            }
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const COLORS: Colors = Colors {
+        foreground: Some(Color4f::new(0.1, 0.1, 0.1, 0.1)),
+        background: Some(Color4f::new(0.2, 0.1, 0.1, 0.1)),
+        special: Some(Color4f::new(0.3, 0.1, 0.1, 0.1)),
+    };
+
+    #[test]
+    fn test_style_for_group() {
+        let mut editor = Editor::new_headless(100, 50);
+        editor
+            .defined_styles
+            .insert(7, Arc::new(Style::new(COLORS)));
+
+        // RUN FUNCTION
+        editor.set_hl_group("Pmenu".to_string(), 7);
+
+        assert_eq!(editor.style_for_group("Pmenu"), Some(&Style::new(COLORS)));
+    }
+
+    #[test]
+    fn test_style_for_unknown_group() {
+        let mut editor = Editor::new_headless(100, 50);
+        editor
+            .defined_styles
+            .insert(7, Arc::new(Style::new(COLORS)));
+        editor.set_hl_group("Pmenu".to_string(), 7);
+
+        assert_eq!(editor.style_for_group("Cursor"), None);
+    }
+
+    fn open_float(editor: &mut Editor, grid: u64, position: (u64, u64), size: (u64, u64)) {
+        let (width, height) = size;
+        let (anchor_column, anchor_row) = position;
+        editor.handle_redraw_event(RedrawEvent::Resize {
+            grid,
+            width,
+            height,
+        });
+        editor.handle_redraw_event(RedrawEvent::WindowFloatPosition {
+            grid,
+            window: grid + 1000,
+            anchor: WindowAnchor::NorthWest,
+            anchor_grid: MAIN_GRID,
+            anchor_row,
+            anchor_column,
+            focusable: true,
+        });
+    }
+
+    #[test]
+    fn test_active_grids() {
+        let mut editor = Editor::new_headless(100, 50);
+        open_float(&mut editor, 2, (10, 5), (20, 10));
+        open_float(&mut editor, 3, (15, 8), (20, 10));
+
+        // RUN FUNCTION
+        assert_eq!(editor.active_grids(), vec![MAIN_GRID, 2, 3]);
+
+        open_float(&mut editor, 2, (0, 0), (20, 10));
+        assert_eq!(editor.active_grids(), vec![MAIN_GRID, 3, 2]);
+
+        editor.handle_redraw_event(RedrawEvent::WindowHide { grid: 3 });
+        assert_eq!(editor.active_grids(), vec![MAIN_GRID, 2]);
+    }
+
+    #[test]
+    fn test_should_dim_single_grid() {
+        let mut editor = Editor::new_headless(100, 50);
+        editor.focused_grid = 2;
+
+        // RUN FUNCTION
+        assert!(!editor.should_dim_grid(MAIN_GRID));
+    }
+
+    #[test]
+    fn test_should_dim_unfocused_grid() {
+        let mut editor = Editor::new_headless(100, 50);
+        open_float(&mut editor, 2, (10, 5), (20, 10));
+        editor.handle_redraw_event(RedrawEvent::CursorGoto {
+            grid: 2,
+            row: 0,
+            column: 0,
+        });
+
+        // RUN FUNCTION
+        assert!(editor.should_dim_grid(MAIN_GRID));
+        assert!(!editor.should_dim_grid(2));
+    }
+
+    #[test]
+    fn test_resize_clamps_cursor() {
+        let mut editor = Editor::new_headless(100, 50);
+        editor.cursor.position = (30, 40);
+
+        // RUN FUNCTION
+        editor.handle_redraw_event(RedrawEvent::Resize {
+            grid: MAIN_GRID,
+            width: 20,
+            height: 20,
+        });
+
+        assert_eq!(editor.cursor.position, (19, 19));
+    }
+
+    #[test]
+    fn test_resize_focused_float_clamps_cursor() {
+        let mut editor = Editor::new_headless(100, 50);
+        open_float(&mut editor, 2, (10, 5), (20, 10));
+        editor.handle_redraw_event(RedrawEvent::CursorGoto {
+            grid: 2,
+            row: 15,
+            column: 8,
+        });
+
+        // RUN FUNCTION
+        editor.handle_redraw_event(RedrawEvent::Resize {
+            grid: 2,
+            width: 10,
+            height: 4,
+        });
+
+        assert_eq!(editor.cursor.position, (9, 3));
+    }
+
+    #[test]
+    fn test_resize_other_grid_keeps_cursor() {
+        let mut editor = Editor::new_headless(100, 50);
+        editor.cursor.position = (30, 40);
+
+        editor.handle_redraw_event(RedrawEvent::Resize {
+            grid: 2,
+            width: 20,
+            height: 20,
+        });
+
+        assert_eq!(editor.cursor.position, (30, 40));
+    }
+
+    #[test]
+    fn test_grid_at_pixel() {
+        let mut editor = Editor::new_headless(100, 50);
+        open_float(&mut editor, 2, (10, 5), (20, 10));
+        open_float(&mut editor, 3, (15, 8), (20, 10));
+
+        // RUN FUNCTION
+        assert_eq!(editor.grid_at_pixel(175.0, 190.0, 10.0, 20.0), Some(3));
+        assert_eq!(editor.grid_at_pixel(115.0, 110.0, 10.0, 20.0), Some(2));
+        assert_eq!(editor.grid_at_pixel(5.0, 5.0, 10.0, 20.0), Some(MAIN_GRID));
+    }
+
+    #[test]
+    fn test_grid_at_pixel_outside() {
+        let mut editor = Editor::new_headless(100, 50);
+        open_float(&mut editor, 2, (10, 5), (20, 10));
+
+        let (width, height) = (editor.grid.width as f32, editor.grid.height as f32);
+        assert_eq!(
+            editor.grid_at_pixel(width * 10.0, height * 20.0, 10.0, 20.0),
+            None
+        );
+        assert_eq!(editor.grid_at_pixel(-1.0, 5.0, 10.0, 20.0), None);
+    }
+
+    #[test]
+    fn test_composite_grid_background() {
+        let mut editor = Editor::new_headless(100, 50);
+        let red = Color4f::new(1.0, 0.0, 0.0, 1.0);
+        let blue = Color4f::new(0.0, 0.0, 1.0, 1.0);
+
+        let main_style = Style::new(Colors::new(None, Some(red.clone()), None));
+        let mut float_style = Style::new(Colors::new(None, Some(blue.clone()), None));
+        float_style.blend = 50;
+        editor.defined_styles.insert(1, Arc::new(main_style));
+        editor.defined_styles.insert(2, Arc::new(float_style));
+
+        editor.handle_redraw_event(RedrawEvent::GridLine {
+            grid: MAIN_GRID,
+            row: 5,
+            column_start: 0,
+            cells: vec![GridLineCell {
+                text: " ".to_string(),
+                highlight_id: Some(1),
+                repeat: Some(100),
+            }],
+        });
+        open_float(&mut editor, 2, (10, 5), (20, 10));
+        editor.handle_redraw_event(RedrawEvent::GridLine {
+            grid: 2,
+            row: 0,
+            column_start: 0,
+            cells: vec![GridLineCell {
+                text: " ".to_string(),
+                highlight_id: Some(2),
+                repeat: Some(20),
+            }],
+        });
+
+        // RUN FUNCTION
+        assert_eq!(
+            editor.composite_grid_background(2, 3, 0),
+            Color4f::new(0.5, 0.0, 0.5, 1.0)
+        );
+        assert_eq!(
+            editor.composite_grid_background(2, 3, 1),
+            editor.default_style.colors.background.clone().unwrap()
+        );
+        assert_eq!(editor.composite_grid_background(MAIN_GRID, 3, 5), red);
+    }
+
+    #[test]
+    fn test_pixel_to_grid_cell() {
+        let mut editor = Editor::new_headless(100, 50);
+        open_float(&mut editor, 2, (10, 3), (20, 10));
+
+        // RUN FUNCTION
+        assert_eq!(
+            editor.pixel_to_grid_cell(2, 155.0, 95.0, 10.0, 20.0),
+            (5, 1)
+        );
+        assert_eq!(
+            editor.pixel_to_grid_cell(2, 100.0, 60.0, 10.0, 20.0),
+            (0, 0)
+        );
+        assert_eq!(
+            editor.pixel_to_grid_cell(MAIN_GRID, 155.0, 95.0, 10.0, 20.0),
+            (15, 4)
+        );
+    }
+
+    #[test]
+    fn test_pixel_to_grid_cell_clamped() {
+        let mut editor = Editor::new_headless(100, 50);
+        open_float(&mut editor, 2, (10, 3), (20, 10));
+
+        assert_eq!(editor.pixel_to_grid_cell(2, 5.0, 5.0, 10.0, 20.0), (0, 0));
+        assert_eq!(
+            editor.pixel_to_grid_cell(2, 1000.0, 1000.0, 10.0, 20.0),
+            (19, 9)
+        );
+    }
+
+    #[test]
+    fn test_draw_grid_line_overflow() {
+        let mut editor = Editor::new_headless(100, 50);
+        editor.handle_redraw_event(RedrawEvent::Resize {
+            grid: MAIN_GRID,
+            width: 5,
+            height: 2,
+        });
+        editor.grid.set_dirty_all(false);
+
+        // RUN FUNCTION
+        editor.handle_redraw_event(RedrawEvent::GridLine {
+            grid: MAIN_GRID,
+            row: 1,
+            column_start: 3,
+            cells: vec![
+                GridLineCell {
+                    text: "abc".to_string(),
+                    highlight_id: None,
+                    repeat: None,
+                },
+                GridLineCell {
+                    text: "d".to_string(),
+                    highlight_id: None,
+                    repeat: None,
+                },
+            ],
+        });
+
+        let row: Vec<Option<&str>> = (0..5)
+            .map(|x| {
+                editor
+                    .grid
+                    .get_cell(x, 1)
+                    .and_then(|cell| cell.as_ref())
+                    .map(|(character, _)| character.as_str())
+            })
+            .collect();
+        assert_eq!(row, vec![None, None, None, Some("a"), Some("b")]);
+        assert!(!editor.grid.is_dirty_cell(2, 1));
+        assert!(editor.grid.is_dirty_cell(3, 1));
+        assert!(editor.grid.is_dirty_cell(4, 1));
+        assert!(editor.grid.get_cell(0, 0).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_mouse_press_timed() {
+        let mut editor = Editor::new_headless(100, 50);
+
+        // RUN FUNCTION
+        assert_eq!(editor.mouse_press_timed(3, 4, "left", 1000), 1);
+        assert_eq!(editor.mouse_press_timed(3, 4, "left", 1200), 2);
+        assert_eq!(editor.mouse_press_timed(3, 4, "left", 1400), 3);
+        assert_eq!(editor.mouse_press_timed(3, 4, "left", 1600), 1);
+    }
+
+    #[test]
+    fn test_mouse_press_timed_resets() {
+        let mut editor = Editor::new_headless(100, 50);
+
+        assert_eq!(editor.mouse_press_timed(3, 4, "left", 1000), 1);
+        assert_eq!(editor.mouse_press_timed(3, 4, "left", 1600), 1);
+        assert_eq!(editor.mouse_press_timed(3, 5, "left", 1700), 1);
+        assert_eq!(editor.mouse_press_timed(3, 5, "right", 1800), 1);
+    }
+
+    #[test]
+    fn test_draw_repeated_default_space() {
+        let mut editor = Editor::new_headless(100, 50);
+        editor.handle_redraw_event(RedrawEvent::GridLine {
+            grid: MAIN_GRID,
+            row: 2,
+            column_start: 0,
+            cells: vec![GridLineCell {
+                text: "x".to_string(),
+                highlight_id: Some(0),
+                repeat: Some(20),
+            }],
+        });
+
+        // RUN FUNCTION
+        editor.handle_redraw_event(RedrawEvent::GridLine {
+            grid: MAIN_GRID,
+            row: 2,
+            column_start: 5,
+            cells: vec![GridLineCell {
+                text: " ".to_string(),
+                highlight_id: Some(0),
+                repeat: Some(10),
+            }],
+        });
+
+        for x in 5..15 {
+            assert_eq!(editor.grid.get_cell(x, 2), Some(&None));
+        }
+        assert_eq!(
+            editor.grid.get_cell(4, 2),
+            Some(&Some(("x".to_string(), None)))
+        );
+        assert_eq!(
+            editor.grid.get_cell(15, 2),
+            Some(&Some(("x".to_string(), None)))
+        );
+    }
+
+    #[test]
+    fn test_set_font_fallbacks() {
+        let mut editor = Editor::new_headless(100, 50);
+        let families = vec!["Fira Code".to_string(), "Noto Color Emoji".to_string()];
+
+        // RUN FUNCTION
+        editor.set_font_fallbacks(families.clone());
+        assert_eq!(editor.font_fallbacks(), families.as_slice());
+
+        editor.set_font_fallbacks(Vec::new());
+        assert!(editor.font_fallbacks().is_empty());
+    }
+
+    #[test]
+    fn test_draw_commands_grouped_by_style() {
+        let mut editor = Editor::new_headless(20, 4);
+        let mut bold = Style::new(COLORS);
+        bold.bold = true;
+        editor
+            .defined_styles
+            .insert(1, Arc::new(Style::new(COLORS)));
+        editor.defined_styles.insert(2, Arc::new(bold.clone()));
+
+        for row in 0..2 {
+            editor.handle_redraw_event(RedrawEvent::GridLine {
+                grid: MAIN_GRID,
+                row,
+                column_start: 0,
+                cells: vec![
+                    GridLineCell {
+                        text: "plain".to_string(),
+                        highlight_id: Some(1),
+                        repeat: None,
+                    },
+                    GridLineCell {
+                        text: "bold".to_string(),
+                        highlight_id: Some(2),
+                        repeat: None,
+                    },
+                ],
+            });
+        }
+
+        // RUN FUNCTION
+        let groups = editor.draw_commands_grouped_by_style();
+
+        assert_eq!(groups.len(), 3);
+        let plain_runs: Vec<&str> = groups[&Style::new(COLORS)]
+            .iter()
+            .map(|command| command.text.as_str())
+            .collect();
+        assert_eq!(plain_runs, vec!["plain", "plain"]);
+        let bold_runs: Vec<&str> = groups[&bold]
+            .iter()
+            .map(|command| command.text.as_str())
+            .collect();
+        assert_eq!(bold_runs, vec!["bold", "bold"]);
+        assert_eq!(groups[editor.default_style.as_ref()].len(), 4);
+    }
+
+    #[test]
+    fn test_build_draw_commands_order() {
+        let mut editor = Editor::new_headless(100, 50);
+        editor.handle_redraw_event(RedrawEvent::Resize {
+            grid: MAIN_GRID,
+            width: 6,
+            height: 2,
+        });
+        editor
+            .defined_styles
+            .insert(1, Arc::new(Style::new(COLORS)));
+
+        for row in 0..2 {
+            editor.handle_redraw_event(RedrawEvent::GridLine {
+                grid: MAIN_GRID,
+                row,
+                column_start: 0,
+                cells: vec![
+                    GridLineCell {
+                        text: "ab".to_string(),
+                        highlight_id: Some(1),
+                        repeat: None,
+                    },
+                    GridLineCell {
+                        text: "cd".to_string(),
+                        highlight_id: Some(0),
+                        repeat: None,
+                    },
+                    GridLineCell {
+                        text: "ef".to_string(),
+                        highlight_id: Some(1),
+                        repeat: None,
+                    },
+                ],
+            });
+        }
+
+        // RUN FUNCTION
+        let (commands, _) = editor.build_draw_commands();
+
+        let positions: Vec<(u64, u64)> = commands
+            .iter()
+            .map(|command| command.grid_position)
+            .collect();
+        assert_eq!(
+            positions,
+            vec![(0, 0), (2, 0), (4, 0), (0, 1), (2, 1), (4, 1)]
+        );
+    }
+
+    #[test]
+    fn test_cell_version() {
+        let mut editor = Editor::new_headless(100, 50);
+        let draw = |editor: &mut Editor, text: &str| {
+            editor.handle_redraw_event(RedrawEvent::GridLine {
+                grid: MAIN_GRID,
+                row: 3,
+                column_start: 4,
+                cells: vec![GridLineCell {
+                    text: text.to_string(),
+                    highlight_id: Some(0),
+                    repeat: None,
+                }],
+            });
+        };
+        let neighbor_version = editor.cell_version(3, 5);
+
+        // RUN FUNCTION
+        draw(&mut editor, "a");
+        let first_version = editor.cell_version(3, 4);
+        assert!(first_version > neighbor_version);
+        draw(&mut editor, "b");
+
+        assert!(editor.cell_version(3, 4) > first_version);
+        assert_eq!(editor.cell_version(3, 5), neighbor_version);
+    }
+
+    #[test]
+    fn test_resume_marks_grid_dirty() {
+        let mut editor = Editor::new_headless(100, 50);
+        editor.handle_redraw_event(RedrawEvent::Suspend);
+        editor.build_draw_commands();
+        assert!(editor.suspended);
+        assert!(!editor.grid.is_dirty_cell(0, 0));
+
+        // RUN FUNCTION
+        editor.set_suspended(false);
+
+        assert!(!editor.suspended);
+        for y in 0..editor.grid.height {
+            assert!(editor.grid.is_dirty_cell(0, y));
+            assert!(editor.grid.is_dirty_cell(editor.grid.width - 1, y));
+        }
+    }
+
+    #[test]
+    fn test_copy_grid_region() {
+        let mut editor = Editor::new_headless(100, 50);
+        editor.handle_redraw_event(RedrawEvent::GridLine {
+            grid: MAIN_GRID,
+            row: 1,
+            column_start: 0,
+            cells: vec![GridLineCell {
+                text: "abcdef".to_string(),
+                highlight_id: Some(0),
+                repeat: None,
+            }],
+        });
+        open_float(&mut editor, 2, (0, 0), (10, 5));
+
+        // RUN FUNCTION
+        editor.copy_grid_region(MAIN_GRID, 2, (1, 1, 4, 2));
+
+        let float = editor.get_grid(2).unwrap();
+        for x in 1..4 {
+            assert_eq!(float.get_cell(x, 1), editor.grid.get_cell(x, 1));
+        }
+        assert_eq!(float.get_cell(0, 1), Some(&None));
+        assert_eq!(float.get_cell(4, 1), Some(&None));
+    }
+
+    #[test]
+    fn test_copy_grid_region_clamped() {
+        let mut editor = Editor::new_headless(100, 50);
+        editor.handle_redraw_event(RedrawEvent::GridLine {
+            grid: MAIN_GRID,
+            row: 4,
+            column_start: 0,
+            cells: vec![GridLineCell {
+                text: "x".to_string(),
+                highlight_id: Some(0),
+                repeat: Some(20),
+            }],
+        });
+        open_float(&mut editor, 2, (0, 0), (10, 5));
+
+        editor.copy_grid_region(MAIN_GRID, 2, (5, 4, 500, 500));
+
+        let float = editor.get_grid(2).unwrap();
+        assert_eq!(float.get_cell(9, 4), Some(&Some(("x".to_string(), None))));
+        assert_eq!(float.get_cell(10, 4), None);
+    }
+
+    #[test]
+    fn test_set_viewport() {
+        let mut editor = Editor::new_headless(100, 50);
+
+        // RUN FUNCTION
+        editor.handle_redraw_event(RedrawEvent::WindowViewport {
+            grid: 2,
+            top_line: 10,
+            bottom_line: 50,
+            current_line: 12,
+            current_column: 4,
+            scroll_delta: 3,
+        });
+
+        assert_eq!(editor.viewport(2), Some(&Viewport::new(10, 50, 12, 4, 3)));
+        assert_eq!(editor.viewport(3), None);
+    }
+
+    #[test]
+    fn test_set_viewport_overwrites() {
+        let mut editor = Editor::new_headless(100, 50);
+        editor.set_viewport(2, Viewport::new(10, 50, 12, 4, 3));
+
+        editor.set_viewport(2, Viewport::new(8, 48, 12, 4, -2));
+
+        assert_eq!(editor.viewport(2), Some(&Viewport::new(8, 48, 12, 4, -2)));
+    }
+
+    #[test]
+    fn test_undefine_style() {
+        let mut editor = Editor::new_headless(100, 50);
+        editor
+            .defined_styles
+            .insert(7, Arc::new(Style::new(COLORS)));
+        editor.set_hl_group("Pmenu".to_string(), 7);
+
+        // RUN FUNCTION
+        editor.undefine_style(7);
+
+        assert_eq!(editor.style_for_group("Pmenu"), None);
+        editor.handle_redraw_event(RedrawEvent::GridLine {
+            grid: MAIN_GRID,
+            row: 0,
+            column_start: 0,
+            cells: vec![GridLineCell {
+                text: "a".to_string(),
+                highlight_id: Some(7),
+                repeat: None,
+            }],
+        });
+        assert_eq!(editor.cell_style(MAIN_GRID, 0, 0), editor.default_style);
+    }
+
+    #[test]
+    fn test_grid_dimensions() {
+        let mut editor = Editor::new_headless(100, 50);
+
+        // RUN FUNCTION
+        editor.handle_redraw_event(RedrawEvent::Resize {
+            grid: MAIN_GRID,
+            width: 30,
+            height: 12,
+        });
+
+        assert_eq!(editor.grid_dimensions(), (30, 12));
+        assert_eq!(
+            editor.grid_dimensions(),
+            (editor.grid.width, editor.grid.height)
+        );
+    }
+
+    #[test]
+    fn test_take_background_changed() {
+        let mut editor = Editor::new_headless(100, 50);
+        let colors = Colors::new(
+            Some(colors::WHITE),
+            Some(Color4f::new(0.2, 0.2, 0.2, 1.0)),
+            Some(colors::GREY),
+        );
+
+        // RUN FUNCTION
+        editor.handle_redraw_event(RedrawEvent::DefaultColorsSet {
+            colors: colors.clone(),
+        });
+        assert!(editor.take_background_changed());
+        assert!(!editor.take_background_changed());
+
+        editor.handle_redraw_event(RedrawEvent::DefaultColorsSet { colors });
+        assert!(!editor.take_background_changed());
+    }
+
+    #[test]
+    fn test_refresh_mode() {
+        let mut editor = Editor::new_headless(100, 50);
+        let mode_status = |mode: &str, blocking: bool| {
+            vec![
+                (Value::from("mode"), Value::from(mode)),
+                (Value::from("blocking"), Value::from(blocking)),
+            ]
+        };
+
+        // RUN FUNCTION
+        editor.refresh_mode(mode_status("i", false));
+        assert_eq!(editor.current_mode, EditorMode::Insert);
+        assert!(editor.cursor.enabled);
+
+        editor.refresh_mode(mode_status("no", true));
+        assert_eq!(editor.current_mode, EditorMode::Unknown("no".to_string()));
+        assert!(!editor.cursor.enabled);
+
+        editor.refresh_mode(mode_status("niI", false));
+        assert_eq!(editor.current_mode, EditorMode::Normal);
+        assert!(editor.cursor.enabled);
+
+        editor.refresh_mode(mode_status("\u{16}", false));
+        assert_eq!(editor.current_mode, EditorMode::Visual);
+
+        editor.refresh_mode(mode_status("t", false));
+        assert_eq!(editor.current_mode, EditorMode::Unknown("t".to_string()));
+    }
+
+    #[test]
+    fn test_draw_wide_character_in_last_column() {
+        let mut editor = Editor::new_headless(100, 50);
+        editor.handle_redraw_event(RedrawEvent::Resize {
+            grid: MAIN_GRID,
+            width: 5,
+            height: 1,
+        });
+
+        // RUN FUNCTION
+        editor.handle_redraw_event(RedrawEvent::GridLine {
+            grid: MAIN_GRID,
+            row: 0,
+            column_start: 2,
+            cells: vec![
+                GridLineCell {
+                    text: "一".to_string(),
+                    highlight_id: Some(0),
+                    repeat: None,
+                },
+                GridLineCell {
+                    text: "".to_string(),
+                    highlight_id: None,
+                    repeat: None,
+                },
+                GridLineCell {
+                    text: "二".to_string(),
+                    highlight_id: None,
+                    repeat: None,
+                },
+                GridLineCell {
+                    text: "".to_string(),
+                    highlight_id: None,
+                    repeat: None,
+                },
+            ],
+        });
+
+        assert_eq!(
+            editor.grid.get_cell(2, 0),
+            Some(&Some(("一".to_string(), None)))
+        );
+        assert_eq!(
+            editor.grid.get_cell(3, 0),
+            Some(&Some(("".to_string(), None)))
+        );
+        assert_eq!(editor.grid.get_cell(4, 0), Some(&None));
+    }
+
+    #[test]
+    fn test_clear_waits_for_flush() {
+        let mut editor = Editor::new_headless(100, 50);
+        editor.build_draw_commands();
+
+        // RUN FUNCTION
+        editor.handle_redraw_event(RedrawEvent::Clear { grid: MAIN_GRID });
+        assert_eq!(editor.build_draw_commands().0.len(), 0);
+        editor.handle_redraw_event(RedrawEvent::GridLine {
+            grid: MAIN_GRID,
+            row: 0,
+            column_start: 0,
+            cells: vec![GridLineCell {
+                text: "hello".to_string(),
+                highlight_id: Some(0),
+                repeat: None,
+            }],
+        });
+        let (commands, should_clear) = editor.build_draw_commands();
+        assert!(commands.is_empty());
+        assert!(!should_clear);
+
+        editor.flush();
+
+        let (commands, should_clear) = editor.build_draw_commands();
+        assert!(should_clear);
+        assert!(commands[0].text.starts_with("hello"));
+        assert_eq!(commands[0].grid_position, (0, 0));
+    }
+
+    #[test]
+    fn test_sanitize_cell_char() {
+        assert_eq!(sanitize_cell_char('\u{0}'), Some("^@".to_string()));
+        assert_eq!(sanitize_cell_char('\u{1}'), Some("^A".to_string()));
+        assert_eq!(sanitize_cell_char('\u{1b}'), Some("^[".to_string()));
+        assert_eq!(sanitize_cell_char('\u{7f}'), Some("^?".to_string()));
+        assert_eq!(sanitize_cell_char('a'), None);
+        assert_eq!(sanitize_cell_char(' '), None);
+        assert_eq!(sanitize_cell_char('一'), None);
+    }
+
+    #[test]
+    fn test_draw_control_character() {
+        let mut editor = Editor::new_headless(100, 50);
+
+        // RUN FUNCTION
+        editor.handle_redraw_event(RedrawEvent::GridLine {
+            grid: MAIN_GRID,
+            row: 0,
+            column_start: 0,
+            cells: vec![GridLineCell {
+                text: "a\u{1}".to_string(),
+                highlight_id: Some(0),
+                repeat: None,
+            }],
+        });
+
+        assert_eq!(
+            editor.grid.get_cell(0, 0),
+            Some(&Some(("a".to_string(), None)))
+        );
+        assert_eq!(
+            editor.grid.get_cell(1, 0),
+            Some(&Some(("^A".to_string(), None)))
+        );
+    }
+
+    #[test]
+    fn test_snapshot_and_detect_scroll() {
+        crate::redraw_scheduler::initialize_settings();
+        let mut editor = Editor::new_headless(5, 6);
+        for row in 0..6 {
+            editor.handle_redraw_event(RedrawEvent::GridLine {
+                grid: MAIN_GRID,
+                row,
+                column_start: 0,
+                cells: vec![GridLineCell {
+                    text: row.to_string(),
+                    highlight_id: Some(0),
+                    repeat: None,
+                }],
+            });
+        }
+        editor.handle_redraw_event(RedrawEvent::Flush);
+        assert_eq!(editor.last_scroll, None);
+
+        // RUN FUNCTION
+        editor.handle_redraw_event(RedrawEvent::Scroll {
+            grid: MAIN_GRID,
+            top: 0,
+            bottom: 6,
+            left: 0,
+            right: 5,
+            rows: 2,
+            columns: 0,
+        });
+        editor.handle_redraw_event(RedrawEvent::Flush);
+        assert_eq!(editor.last_scroll, Some(2));
+
+        editor.last_scroll = None;
+        editor.handle_redraw_event(RedrawEvent::GridLine {
+            grid: MAIN_GRID,
+            row: 1,
+            column_start: 3,
+            cells: vec![GridLineCell {
+                text: "x".to_string(),
+                highlight_id: Some(0),
+                repeat: None,
+            }],
+        });
+        editor.handle_redraw_event(RedrawEvent::Flush);
+        assert_eq!(editor.last_scroll, None);
+    }
+
+    #[test]
+    fn test_draw_explicit_default_highlight() {
+        let mut editor = Editor::new_headless(100, 50);
+        editor
+            .defined_styles
+            .insert(1, Arc::new(Style::new(COLORS)));
+
+        // RUN FUNCTION
+        editor.handle_redraw_event(RedrawEvent::GridLine {
+            grid: MAIN_GRID,
+            row: 0,
+            column_start: 0,
+            cells: vec![
+                GridLineCell {
+                    text: "a".to_string(),
+                    highlight_id: Some(1),
+                    repeat: None,
+                },
+                GridLineCell {
+                    text: "b".to_string(),
+                    highlight_id: None,
+                    repeat: None,
+                },
+                GridLineCell {
+                    text: "c".to_string(),
+                    highlight_id: Some(0),
+                    repeat: None,
+                },
+                GridLineCell {
+                    text: "d".to_string(),
+                    highlight_id: None,
+                    repeat: None,
+                },
+            ],
+        });
+
+        let style = Some(Arc::new(Style::new(COLORS)));
+        assert_eq!(
+            editor.grid.get_cell(1, 0),
+            Some(&Some(("b".to_string(), style)))
+        );
+        assert_eq!(
+            editor.grid.get_cell(2, 0),
+            Some(&Some(("c".to_string(), None)))
+        );
+        assert_eq!(
+            editor.grid.get_cell(3, 0),
+            Some(&Some(("d".to_string(), None)))
+        );
+    }
+
+    #[test]
+    fn test_clamp_grid_size() {
+        let mut editor = Editor::new_headless(100, 50);
+
+        // RUN FUNCTION
+        editor.set_min_grid_size(12, 3);
+
+        assert_eq!(editor.clamp_grid_size(5, 1), (12, 3));
+        assert_eq!(editor.clamp_grid_size(80, 2), (80, 3));
+        assert_eq!(editor.clamp_grid_size(80, 24), (80, 24));
+
+        editor.set_min_grid_size(0, 0);
+        assert_eq!(editor.clamp_grid_size(0, 0), (1, 1));
+    }
+
+    #[test]
+    fn test_grid_size_for_window() {
+        let mut editor = Editor::new_headless(100, 50);
+
+        // RUN FUNCTION
+        assert_eq!(
+            editor.grid_size_for_window((799, 479), (10.0, 20.0)),
+            (80, 24)
+        );
+        assert_eq!(editor.grid_size_for_window((5, 5), (10.0, 20.0)), (1, 1));
+
+        editor.set_min_grid_size(12, 3);
+        assert_eq!(editor.grid_size_for_window((50, 40), (10.0, 20.0)), (12, 3));
+        assert_eq!(
+            editor.grid_size_for_window((799, 479), (10.0, 20.0)),
+            (80, 24)
+        );
+    }
+    #[test]
+    fn test_resolve_cell_render_style() {
+        let mut editor = Editor::new_headless(100, 50);
+        let mut search_style = Style::new(Colors::new(
+            None,
+            Some(Color4f::new(1.0, 1.0, 0.0, 1.0)),
+            None,
+        ));
+        search_style.blend = 50;
+        editor.defined_styles.insert(1, Arc::new(search_style));
+        editor
+            .defined_styles
+            .insert(2, Arc::new(Style::new(COLORS)));
+        editor.handle_redraw_event(RedrawEvent::GridLine {
+            grid: MAIN_GRID,
+            row: 3,
+            column_start: 0,
+            cells: vec![
+                GridLineCell {
+                    text: "a".to_string(),
+                    highlight_id: Some(1),
+                    repeat: None,
+                },
+                GridLineCell {
+                    text: "b".to_string(),
+                    highlight_id: Some(2),
+                    repeat: None,
+                },
+            ],
+        });
+
+        // RUN FUNCTION
+        let resolved = editor.resolve_cell_render_style(3, 0);
+
+        assert_eq!(
+            resolved.colors.background,
+            Some(Color4f::new(0.5, 0.5, 0.0, 1.0))
+        );
+        assert_eq!(resolved.blend, 0);
+        assert_eq!(editor.resolve_cell_render_style(3, 1), Style::new(COLORS));
+    }
+
+    #[test]
+    fn test_apply_grid_lines() {
+        let mut editor = Editor::new_headless(100, 50);
+        editor
+            .defined_styles
+            .insert(1, Arc::new(Style::new(COLORS)));
+        let cell = |text: &str, highlight_id: Option<u64>| GridLineCell {
+            text: text.to_string(),
+            highlight_id,
+            repeat: None,
+        };
+
+        // RUN FUNCTION
+        editor.apply_grid_lines(vec![
+            (MAIN_GRID, 0, 0, vec![cell("a", Some(1)), cell("b", None)]),
+            (MAIN_GRID, 1, 0, vec![cell("c", None)]),
+            (MAIN_GRID, 0, 2, vec![cell("d", Some(0)), cell("e", None)]),
+        ]);
+
+        let style = Some(Arc::new(Style::new(COLORS)));
+        assert_eq!(
+            editor.grid.get_cell(1, 0),
+            Some(&Some(("b".to_string(), style)))
+        );
+        assert_eq!(
+            editor.grid.get_cell(0, 1),
+            Some(&Some(("c".to_string(), None)))
+        );
+        assert_eq!(
+            editor.grid.get_cell(3, 0),
+            Some(&Some(("e".to_string(), None)))
+        );
+    }
+
+    #[test]
+    fn test_is_float() {
+        let mut editor = Editor::new_headless(100, 50);
+
+        // RUN FUNCTION
+        open_float(&mut editor, 3, (10, 5), (20, 10));
+        assert!(editor.is_float(3));
+
+        editor.handle_redraw_event(RedrawEvent::WindowPosition {
+            grid: 3,
+            window: 1003,
+            start_row: 0,
+            start_column: 0,
+            width: 20,
+            height: 10,
+        });
+        assert!(!editor.is_float(3));
+        assert!(!editor.is_float(MAIN_GRID));
+    }
+
+    #[test]
+    fn test_reclamp_grid_positions() {
+        let mut editor = Editor::new_headless(100, 50);
+        open_float(&mut editor, 2, (90, 40), (10, 5));
+        open_float(&mut editor, 3, (5, 5), (10, 5));
+
+        // RUN FUNCTION
+        editor.handle_redraw_event(RedrawEvent::Resize {
+            grid: MAIN_GRID,
+            width: 60,
+            height: 20,
+        });
+
+        assert_eq!(editor.grid_position(2), (59, 19));
+        assert_eq!(editor.grid_position(3), (5, 5));
+    }
+
+    #[test]
+    fn test_wildmenu() {
+        let mut editor = Editor::new_headless(100, 50);
+        let items = vec!["edit".to_string(), "echo".to_string()];
+
+        // RUN FUNCTION
+        editor.handle_redraw_event(RedrawEvent::WildmenuShow {
+            items: items.clone(),
+        });
+        assert_eq!(editor.wildmenu_items, items);
+        assert_eq!(editor.wildmenu_selected, None);
+
+        editor.handle_redraw_event(RedrawEvent::WildmenuSelect { selected: 1 });
+        assert_eq!(editor.wildmenu_selected, Some(1));
+
+        editor.handle_redraw_event(RedrawEvent::WildmenuSelect { selected: 2 });
+        assert_eq!(editor.wildmenu_selected, Some(1));
+
+        editor.handle_redraw_event(RedrawEvent::WildmenuSelect { selected: -1 });
+        assert_eq!(editor.wildmenu_selected, None);
+
+        editor.handle_redraw_event(RedrawEvent::WildmenuHide);
+        assert!(editor.wildmenu_items.is_empty());
+        assert_eq!(editor.wildmenu_selected, None);
+    }
+
+    #[test]
+    fn test_cursor_goto_updates_focus_and_viewport() {
+        let mut editor = Editor::new_headless(100, 50);
+        open_float(&mut editor, 2, (10, 5), (20, 10));
+        editor.set_viewport(2, Viewport::new(10, 20, 10, 0, 0));
+
+        // RUN FUNCTION
+        editor.handle_redraw_event(RedrawEvent::CursorGoto {
+            grid: 2,
+            row: 4,
+            column: 3,
+        });
+
+        assert_eq!(editor.focused_grid, 2);
+        assert_eq!(editor.cursor.position, (4, 3));
+        assert_eq!(editor.viewport(2).unwrap().current_line, 13);
+        assert!(editor.should_dim_grid(MAIN_GRID));
+        assert!(!editor.should_dim_grid(2));
+    }
+
+    #[test]
+    fn test_fill_row_gaps() {
+        let mut editor = Editor::new_headless(100, 50);
+        editor
+            .defined_styles
+            .insert(1, Arc::new(Style::new(COLORS)));
+        editor.handle_redraw_event(RedrawEvent::GridLine {
+            grid: MAIN_GRID,
+            row: 0,
+            column_start: 0,
+            cells: vec![GridLineCell {
+                text: "ab".to_string(),
+                highlight_id: Some(1),
+                repeat: None,
+            }],
+        });
+        editor.handle_redraw_event(RedrawEvent::GridLine {
+            grid: MAIN_GRID,
+            row: 0,
+            column_start: 5,
+            cells: vec![GridLineCell {
+                text: "cd".to_string(),
+                highlight_id: Some(1),
+                repeat: None,
+            }],
+        });
+
+        // RUN FUNCTION
+        editor.fill_row_gaps = true;
+        let (commands, _) = editor.build_draw_commands();
+
+        let style = Some(Arc::new(Style::new(COLORS)));
+        assert_eq!(commands[0].text, "ab   cd");
+        assert_eq!(commands[0].style, style);
+        assert_eq!(commands[1].grid_position, (7, 0));
+        assert_eq!(commands[1].style, None);
+        // Two runs in the first row and one in each blank row
+        assert_eq!(editor.draw_command_count(), 2 + 49);
+    }
+
+    #[test]
+    fn test_next_blink_transition_ms() {
+        let mut editor = Editor::new_headless(100, 50);
+        editor.cursor.blinkon = Some(400);
+        editor.cursor.blinkoff = Some(250);
+
+        // RUN FUNCTION
+        assert_eq!(editor.next_blink_transition_ms(0), Some(400));
+        assert_eq!(editor.next_blink_transition_ms(100), Some(300));
+        assert_eq!(editor.next_blink_transition_ms(400), Some(250));
+        assert_eq!(editor.next_blink_transition_ms(500), Some(150));
+        assert_eq!(editor.next_blink_transition_ms(650), Some(400));
+
+        editor.cursor.blinkwait = Some(700);
+        assert_eq!(editor.next_blink_transition_ms(200), Some(500));
+        assert_eq!(editor.next_blink_transition_ms(800), Some(300));
+    }
+
+    #[test]
+    fn test_next_blink_transition_ms_disabled() {
+        let mut editor = Editor::new_headless(100, 50);
+        assert_eq!(editor.next_blink_transition_ms(0), None);
+
+        editor.cursor.blinkon = Some(400);
+        editor.cursor.blinkoff = Some(0);
+        assert_eq!(editor.next_blink_transition_ms(0), None);
+
+        editor.cursor.blinkoff = Some(250);
+        editor.cursor.blinkwait = Some(0);
+        assert_eq!(editor.next_blink_transition_ms(0), None);
+    }
+
+    #[test]
+    fn test_resolved_style() {
+        let default_colors = Colors::new(
+            Some(colors::WHITE),
+            Some(Color4f::new(0.0, 0.0, 0.0, 1.0)),
+            Some(colors::GREY),
+        );
+        let mut style = Style::new(Colors::new(
+            Some(Color4f::new(1.0, 0.0, 0.0, 1.0)),
+            Some(Color4f::new(0.0, 0.0, 1.0, 1.0)),
+            None,
+        ));
+        style.reverse = true;
+        style.blend = 50;
+        style.bold = true;
+        let command = DrawCommand::new("a".to_string(), 1, (0, 0), Some(Arc::new(style)));
+
+        // RUN FUNCTION
+        let resolved = command.resolved_style(&default_colors);
+
+        assert_eq!(resolved.foreground, Color4f::new(0.0, 0.0, 1.0, 1.0));
+        assert_eq!(resolved.background, Color4f::new(0.5, 0.0, 0.0, 1.0));
+        assert_eq!(resolved.special, colors::GREY);
+        assert!(resolved.bold);
+        assert!(!resolved.italic);
+    }
+
+    #[test]
+    fn test_resolved_style_plain() {
+        let command = DrawCommand::new(
+            "a".to_string(),
+            1,
+            (0, 0),
+            Some(Arc::new(Style::new(COLORS))),
+        );
+
+        let resolved = command.resolved_style(&COLORS);
+
+        assert_eq!(resolved.foreground, COLORS.foreground.clone().unwrap());
+        assert_eq!(resolved.background, COLORS.background.clone().unwrap());
+        assert_eq!(resolved.special, COLORS.special.clone().unwrap());
+    }
+
+    #[test]
+    fn test_draw_grid_line_column_out_of_bounds() {
+        let mut editor = Editor::new_headless(100, 50);
+        editor.handle_redraw_event(RedrawEvent::Resize {
+            grid: MAIN_GRID,
+            width: 5,
+            height: 2,
+        });
+        editor.grid.set_dirty_all(false);
+
+        // RUN FUNCTION
+        editor.handle_redraw_event(RedrawEvent::GridLine {
+            grid: MAIN_GRID,
+            row: 0,
+            column_start: 5,
+            cells: vec![GridLineCell {
+                text: "a".to_string(),
+                highlight_id: None,
+                repeat: None,
+            }],
+        });
+
+        assert_eq!(editor.out_of_bounds_lines, 1);
+        assert!(editor
+            .grid
+            .rows()
+            .all(|row| row.iter().all(|cell| cell.is_none())));
+        assert!(!editor.grid.is_dirty_cell(4, 0));
+    }
+
+    #[test]
+    fn test_diff_grids() {
+        let mut editor = Editor::new_headless(100, 50);
+        let mut other = Editor::new_headless(100, 50);
+        for editor in [&mut editor, &mut other].iter_mut() {
+            editor.handle_redraw_event(RedrawEvent::Resize {
+                grid: MAIN_GRID,
+                width: 4,
+                height: 2,
+            });
+        }
+
+        // RUN FUNCTION
+        assert!(editor.diff_grids(&other).is_empty());
+
+        other.apply_grid_lines(vec![(
+            MAIN_GRID,
+            1,
+            2,
+            vec![GridLineCell {
+                text: "a".to_string(),
+                highlight_id: None,
+                repeat: None,
+            }],
+        )]);
+        assert_eq!(editor.diff_grids(&other), vec![(2, 1)]);
+
+        other.handle_redraw_event(RedrawEvent::Resize {
+            grid: MAIN_GRID,
+            width: 4,
+            height: 1,
+        });
+        assert_eq!(editor.diff_grids(&other).len(), 8);
+    }
+
+    #[test]
+    fn test_set_mode_info() {
+        let mut editor = Editor::new_headless(100, 50);
+        let mut modes = HashMap::new();
+        modes.insert(
+            "normal".to_string(),
+            CursorMode {
+                name: Some("normal".to_string()),
+                shape: Some(CursorShape::Block),
+                blinkon: Some(400),
+                blinkoff: Some(250),
+                ..Default::default()
+            },
+        );
+        modes.insert(
+            "insert".to_string(),
+            CursorMode {
+                name: Some("insert".to_string()),
+                shape: Some(CursorShape::Vertical),
+                cell_percentage: Some(0.25),
+                blinkon: Some(100),
+                blinkoff: Some(50),
+                ..Default::default()
+            },
+        );
+
+        // RUN FUNCTION
+        editor.set_mode_info(modes);
+
+        assert!(editor.set_mode("insert"));
+        assert_eq!(editor.cursor.shape, CursorShape::Vertical);
+        assert_eq!(editor.cursor.cell_percentage, Some(0.25));
+        assert_eq!(editor.cursor.blinkon, Some(100));
+        assert_eq!(editor.cursor.blinkoff, Some(50));
+
+        assert!(editor.set_mode("normal"));
+        assert_eq!(editor.cursor.shape, CursorShape::Block);
+        assert_eq!(editor.cursor.cell_percentage, None);
+        assert_eq!(editor.cursor.blinkon, Some(400));
+        assert_eq!(editor.cursor.blinkoff, Some(250));
+
+        assert!(!editor.set_mode("replace"));
+        assert_eq!(editor.cursor.blinkon, Some(400));
+    }
+
+    #[test]
+    fn test_should_animate_last_scroll() {
+        let mut editor = Editor::new_headless(100, 50);
+        editor.set_max_scroll_animation_distance(100);
+        assert!(!editor.should_animate_last_scroll());
+
+        // RUN FUNCTION
+        editor.set_viewport(MAIN_GRID, Viewport::new(3, 53, 3, 0, 3));
+        assert!(editor.should_animate_last_scroll());
+
+        editor.set_viewport(MAIN_GRID, Viewport::new(10003, 10053, 10003, 0, 10000));
+        assert!(!editor.should_animate_last_scroll());
+
+        editor.set_viewport(MAIN_GRID, Viewport::new(9903, 9953, 9903, 0, -100));
+        assert!(editor.should_animate_last_scroll());
+    }
+
+    #[test]
+    fn test_cursor_line_text() {
+        let mut editor = Editor::new_headless(100, 50);
+        editor.handle_redraw_event(RedrawEvent::Resize {
+            grid: MAIN_GRID,
+            width: 10,
+            height: 3,
+        });
+        editor.apply_grid_lines(vec![(
+            MAIN_GRID,
+            1,
+            1,
+            vec![GridLineCell {
+                text: "let x".to_string(),
+                highlight_id: None,
+                repeat: None,
+            }],
+        )]);
+
+        // RUN FUNCTION
+        editor.handle_redraw_event(RedrawEvent::CursorGoto {
+            grid: MAIN_GRID,
+            row: 4,
+            column: 1,
+        });
+        assert_eq!(editor.cursor_line_text(), " let x");
+
+        editor.cursor.position = (0, 3);
+        assert_eq!(editor.cursor_line_text(), "");
+    }
+
+    #[test]
+    fn test_grid_opacity() {
+        let mut editor = Editor::new_headless(100, 50);
+
+        // RUN FUNCTION
+        editor.set_grid_opacity(2, 0.5);
+        assert_eq!(editor.grid_opacity(2), 0.5);
+        assert_eq!(editor.grid_opacity(3), 1.0);
+
+        editor.set_grid_opacity(2, 2.0);
+        assert_eq!(editor.grid_opacity(2), 1.0);
+    }
+
+    #[test]
+    fn test_draw_command_count() {
+        let mut editor = Editor::new_headless(100, 50);
+        editor.handle_redraw_event(RedrawEvent::Resize {
+            grid: MAIN_GRID,
+            width: 4,
+            height: 2,
+        });
+        editor
+            .defined_styles
+            .insert(1, Arc::new(Style::new(COLORS)));
+        let mut bold = Style::new(COLORS);
+        bold.bold = true;
+        editor.defined_styles.insert(2, Arc::new(bold));
+        let cell = |text: &str, highlight_id: u64| GridLineCell {
+            text: text.to_string(),
+            highlight_id: Some(highlight_id),
+            repeat: None,
+        };
+        assert_eq!(editor.draw_command_count(), 2);
+
+        // RUN FUNCTION
+        editor.apply_grid_lines(vec![(
+            MAIN_GRID,
+            0,
+            0,
+            vec![cell("a", 1), cell("b", 2), cell("c", 1), cell("d", 2)],
+        )]);
+        assert_eq!(editor.draw_command_count(), 5);
+
+        editor.apply_grid_lines(vec![(MAIN_GRID, 1, 0, vec![cell("ef", 1)])]);
+        assert_eq!(editor.draw_command_count(), 6);
+
+        editor.apply_grid_lines(vec![(
+            MAIN_GRID,
+            1,
+            0,
+            vec![cell("中", 1), cell("", 1), cell("g", 1)],
+        )]);
+        assert_eq!(editor.draw_command_count(), 7);
+        assert_eq!(
+            editor.draw_command_count(),
+            editor.grid_draw_commands().len()
+        );
+    }
+
+    #[test]
+    fn test_columns_and_rows() {
+        let mut editor = Editor::new_headless(100, 50);
+        assert_eq!((editor.columns(), editor.rows()), (100, 50));
+
+        // RUN FUNCTION
+        editor.handle_redraw_event(RedrawEvent::Resize {
+            grid: MAIN_GRID,
+            width: 80,
+            height: 24,
+        });
+        assert_eq!(editor.columns(), 80);
+        assert_eq!(editor.rows(), 24);
+    }
+
+    #[test]
+    fn test_set_color_filter() {
+        let mut editor = Editor::new_headless(20, 4);
+        editor.build_draw_commands();
+
+        // RUN FUNCTION
+        editor.set_color_filter(ColorFilter::new(0.0, 0.8));
+        assert_eq!(editor.color_filter, ColorFilter::new(0.0, 0.8));
+
+        let (draw_commands, should_clear) = editor.build_draw_commands();
+        assert!(should_clear);
+        assert_eq!(draw_commands.len(), 4);
+
+        editor.set_color_filter(ColorFilter::new(0.0, 0.8));
+        assert!(editor.build_draw_commands().0.is_empty());
+    }
+
+    #[test]
+    fn test_uniform_run_shares_style() {
+        let mut editor = Editor::new_headless(100, 50);
+        editor.handle_redraw_event(RedrawEvent::Resize {
+            grid: MAIN_GRID,
+            width: 100,
+            height: 1,
+        });
+        editor
+            .defined_styles
+            .insert(1, Arc::new(Style::new(COLORS)));
+        let style = editor.defined_styles.get(&1).cloned().unwrap();
+
+        // RUN FUNCTION
+        editor.apply_grid_lines(vec![(
+            MAIN_GRID,
+            0,
+            0,
+            vec![GridLineCell {
+                text: "a".to_string(),
+                highlight_id: Some(1),
+                repeat: Some(100),
+            }],
+        )]);
+        let draw_commands = editor.grid_draw_commands();
+
+        assert_eq!(draw_commands.len(), 1);
+        assert_eq!(draw_commands[0].text, "a".repeat(100));
+        assert_eq!(draw_commands[0].cell_width, 100);
+        assert_eq!(draw_commands[0].style, Some(style.clone()));
+        // Every cell shares the first cell's allocation rather than holding its own copy
+        let cell_style = |x: u64| match editor.grid.get_cell(x, 0) {
+            Some(Some((_, Some(style)))) => style.clone(),
+            _ => panic!("Expected a styled cell"),
+        };
+        let first_style = cell_style(0);
+        for x in 1..100 {
+            assert!(Arc::ptr_eq(&cell_style(x), &first_style));
+        }
+    }
+
+    #[test]
+    fn test_row_is_uniform() {
+        let mut editor = Editor::new_headless(100, 50);
+        editor.handle_redraw_event(RedrawEvent::Resize {
+            grid: MAIN_GRID,
+            width: 6,
+            height: 3,
+        });
+        editor
+            .defined_styles
+            .insert(1, Arc::new(Style::new(COLORS)));
+        let cell = |text: &str, repeat: Option<u64>| GridLineCell {
+            text: text.to_string(),
+            highlight_id: Some(1),
+            repeat,
+        };
+
+        // RUN FUNCTION
+        editor.apply_grid_lines(vec![
+            (MAIN_GRID, 0, 0, vec![cell("\u{2500}", Some(6))]),
+            (
+                MAIN_GRID,
+                1,
+                0,
+                vec![cell("\u{2500}", Some(5)), cell("x", None)],
+            ),
+        ]);
+
+        assert_eq!(
+            editor.row_is_uniform(0),
+            Some(('\u{2500}', Style::new(COLORS)))
+        );
+        assert_eq!(editor.row_is_uniform(1), None);
+        assert_eq!(editor.row_is_uniform(2), None);
+        assert_eq!(editor.row_is_uniform(3), None);
+    }
+
+    #[test]
+    fn test_snap_cursor() {
+        let mut editor = Editor::new_headless(100, 50);
+        editor.set_max_scroll_animation_distance(100);
+        assert!(!editor.take_cursor_snap());
+
+        // RUN FUNCTION
+        editor.snap_cursor();
+        assert!(editor.take_cursor_snap());
+        assert!(!editor.take_cursor_snap());
+
+        editor.set_viewport(MAIN_GRID, Viewport::new(3, 53, 3, 0, 3));
+        assert!(!editor.take_cursor_snap());
+
+        editor.set_viewport(MAIN_GRID, Viewport::new(10003, 10053, 10003, 0, 10000));
+        assert!(editor.take_cursor_snap());
+    }
+
+    #[test]
+    fn test_grid_background() {
+        let mut editor = Editor::new_headless(100, 50);
+        let green = Color4f::new(0.0, 1.0, 0.0, 1.0);
+        open_float(&mut editor, 2, (10, 5), (20, 10));
+
+        // RUN FUNCTION
+        editor.set_grid_background(2, Some(green.clone()));
+
+        assert_eq!(editor.composite_grid_background(2, 3, 1), green);
+        assert_eq!(
+            editor.composite_grid_background(MAIN_GRID, 3, 1),
+            editor.default_style.colors.background.clone().unwrap()
+        );
+
+        editor.set_grid_background(2, None);
+        assert_eq!(
+            editor.composite_grid_background(2, 3, 1),
+            editor.default_style.colors.background.clone().unwrap()
+        );
+    }
+
+    #[test]
+    fn test_pixel_size() {
+        let mut editor = Editor::new_headless(100, 50);
+        editor.handle_redraw_event(RedrawEvent::Resize {
+            grid: MAIN_GRID,
+            width: 80,
+            height: 24,
+        });
+
+        // RUN FUNCTION
+        assert_eq!(editor.pixel_height(20.0), 480.0);
+        assert_eq!(editor.pixel_width(10.0), 800.0);
+
+        editor.handle_redraw_event(RedrawEvent::OptionSet {
+            gui_option: GuiOption::LineSpace(2),
+        });
+        assert_eq!(editor.pixel_height(20.0), 528.0);
+        assert_eq!(editor.pixel_width(10.0), 800.0);
+
+        editor.handle_redraw_event(RedrawEvent::OptionSet {
+            gui_option: GuiOption::LineSpace(-4),
+        });
+        assert_eq!(editor.pixel_height(20.0), 384.0);
+    }
+
+    #[test]
+    fn test_empty_cell_keeps_previous_style() {
+        let mut editor = Editor::new_headless(100, 50);
+        let style = Arc::new(Style::new(COLORS));
+        editor.defined_styles.insert(1, style.clone());
+        let mut column_pos = 0;
+
+        // RUN FUNCTION
+        editor.draw_grid_line_cell(
+            MAIN_GRID,
+            0,
+            &mut column_pos,
+            GridLineCell {
+                text: "a".to_string(),
+                highlight_id: Some(1),
+                repeat: None,
+            },
+        );
+        editor.draw_grid_line_cell(
+            MAIN_GRID,
+            0,
+            &mut column_pos,
+            GridLineCell {
+                text: "".to_string(),
+                highlight_id: None,
+                repeat: None,
+            },
+        );
+
+        assert_eq!(editor.previous_style, Some(style));
+        assert_eq!(column_pos, 2);
+    }
+
+    #[test]
+    fn test_accessibility_snapshot() {
+        let mut editor = Editor::new_headless(100, 50);
+        editor.handle_redraw_event(RedrawEvent::Resize {
+            grid: MAIN_GRID,
+            width: 3,
+            height: 2,
+        });
+        editor
+            .defined_styles
+            .insert(1, Arc::new(Style::new(COLORS)));
+        editor.apply_grid_lines(vec![(
+            MAIN_GRID,
+            0,
+            1,
+            vec![GridLineCell {
+                text: "x".to_string(),
+                highlight_id: Some(1),
+                repeat: None,
+            }],
+        )]);
+        let default_colors = editor.default_style.colors.clone();
+
+        // RUN FUNCTION
+        let snapshot = editor.accessibility_snapshot();
+
+        assert_eq!(snapshot.len(), 2);
+        assert_eq!(snapshot[0].len(), 3);
+        assert_eq!(
+            snapshot[0][1],
+            (
+                'x',
+                COLORS.foreground.clone().unwrap(),
+                COLORS.background.clone().unwrap()
+            )
+        );
+        assert_eq!(
+            snapshot[1][2],
+            (
+                ' ',
+                default_colors.foreground.unwrap(),
+                default_colors.background.unwrap()
+            )
+        );
+    }
+
+    #[test]
+    fn test_operator_pending_mode() {
+        let mut editor = Editor::new_headless(100, 50);
+        let normal = CursorMode {
+            name: Some("normal".to_string()),
+            shape: Some(CursorShape::Block),
+            ..Default::default()
+        };
+        let operator = CursorMode {
+            name: Some("operator".to_string()),
+            shape: Some(CursorShape::Vertical),
+            cell_percentage: Some(0.2),
+            ..Default::default()
+        };
+        editor.handle_redraw_event(RedrawEvent::ModeInfoSet {
+            cursor_modes: vec![normal, operator],
+        });
+
+        // RUN FUNCTION
+        editor.handle_redraw_event(RedrawEvent::ModeChange {
+            mode: EditorMode::Unknown("operator".to_string()),
+            mode_index: 1,
+        });
+
+        assert_eq!(editor.cursor.shape, CursorShape::Vertical);
+        assert_eq!(editor.cursor.cell_percentage, Some(0.2));
+        assert_eq!(
+            editor.current_mode,
+            EditorMode::Unknown("operator".to_string())
+        );
+
+        assert!(editor.set_mode("normal"));
+        assert_eq!(editor.cursor.shape, CursorShape::Block);
+        assert_eq!(editor.cursor.cell_percentage, None);
+    }
+
+    #[test]
+    fn test_word_at_cursor() {
+        let mut editor = Editor::new_headless(100, 50);
+        editor.handle_redraw_event(RedrawEvent::Resize {
+            grid: MAIN_GRID,
+            width: 12,
+            height: 1,
+        });
+        editor.apply_grid_lines(vec![(
+            MAIN_GRID,
+            0,
+            0,
+            vec![GridLineCell {
+                text: "foo my_var2".to_string(),
+                highlight_id: None,
+                repeat: None,
+            }],
+        )]);
+
+        // RUN FUNCTION
+        editor.cursor.position = (6, 0);
+        assert_eq!(editor.word_at_cursor(), Some((4, 11)));
+
+        editor.cursor.position = (0, 0);
+        assert_eq!(editor.word_at_cursor(), Some((0, 3)));
+
+        editor.cursor.position = (3, 0);
+        assert_eq!(editor.word_at_cursor(), None);
+
+        editor.cursor.position = (11, 0);
+        assert_eq!(editor.word_at_cursor(), None);
+    }
+
+    #[test]
+    fn test_resolve_style_once_per_run() {
+        let mut editor = Editor::new_headless(100, 50);
+        editor
+            .defined_styles
+            .insert(1, Arc::new(Style::new(COLORS)));
+        let cells = (0..10)
+            .map(|_| GridLineCell {
+                text: "a".to_string(),
+                highlight_id: Some(1),
+                repeat: None,
+            })
+            .collect();
+
+        // RUN FUNCTION
+        editor.apply_grid_lines(vec![(MAIN_GRID, 0, 0, cells)]);
+
+        assert_eq!(editor.style_lookups, 1);
+        for x in 0..10 {
+            assert_eq!(
+                editor.grid.get_cell(x, 0).unwrap().clone().unwrap().1,
+                editor.defined_styles.get(&1).cloned()
+            );
+        }
+
+        let mut bold = Style::new(COLORS);
+        bold.bold = true;
+        editor.handle_redraw_event(RedrawEvent::HighlightAttributesDefine { id: 1, style: bold });
+        editor.apply_grid_lines(vec![(
+            MAIN_GRID,
+            1,
+            0,
+            vec![GridLineCell {
+                text: "b".to_string(),
+                highlight_id: Some(1),
+                repeat: None,
+            }],
+        )]);
+        let (_, style) = editor.grid.get_cell(0, 1).unwrap().clone().unwrap();
+        assert!(style.unwrap().bold);
+    }
+
+    #[test]
+    fn test_mark_all_dirty() {
+        let mut editor = Editor::new_headless(100, 50);
+        editor.handle_redraw_event(RedrawEvent::Resize {
+            grid: MAIN_GRID,
+            width: 5,
+            height: 4,
+        });
+        open_float(&mut editor, 2, (1, 1), (3, 2));
+        editor.build_draw_commands();
+        editor.grids.get_mut(&2).unwrap().set_dirty_all(false);
+        assert!(!editor.grid.is_dirty_cell(0, 0));
+
+        // RUN FUNCTION
+        editor.mark_all_dirty();
+
+        for y in 0..4 {
+            assert!((0..5).all(|x| editor.grid.is_dirty_cell(x, y)));
+        }
+        assert!(editor.grids[&2].is_dirty_cell(2, 1));
+        assert_eq!(editor.build_draw_commands().0.len(), 4);
+    }
+
+    #[test]
+    fn test_animate_grid_positions() {
+        let mut editor = Editor::new_headless(100, 50);
+        open_float(&mut editor, 2, (10, 5), (20, 10));
+        assert!(!editor.animate_grid_positions(0.016, 10.0));
+        assert_eq!(editor.grid_render_position(2), (10.0, 5.0));
+
+        // RUN FUNCTION
+        editor.grid_positions.insert(2, (20, 0));
+        assert!(editor.animate_grid_positions(0.05, 10.0));
+        assert_eq!(editor.grid_render_position(2), (15.0, 2.5));
+
+        let mut steps = 0;
+        while editor.animate_grid_positions(0.05, 10.0) {
+            steps += 1;
+            assert!(steps < 100);
+        }
+        assert_eq!(editor.grid_render_position(2), (20.0, 0.0));
+    }
+
+    #[test]
+    fn test_wide_character_replaces_stale_text() {
+        let mut editor = Editor::new_headless(100, 50);
+        let cell = |text: &str| GridLineCell {
+            text: text.to_string(),
+            highlight_id: Some(0),
+            repeat: None,
+        };
+        editor.apply_grid_lines(vec![(MAIN_GRID, 0, 0, vec![cell("abc")])]);
+
+        // RUN FUNCTION
+        editor.apply_grid_lines(vec![(MAIN_GRID, 0, 0, vec![cell("一"), cell("")])]);
+
+        assert_eq!(
+            editor.grid.get_cell(0, 0),
+            Some(&Some(("一".to_string(), None)))
+        );
+        assert_eq!(
+            editor.grid.get_cell(1, 0),
+            Some(&Some(("".to_string(), None)))
+        );
+        assert_eq!(
+            editor.grid.get_cell(2, 0),
+            Some(&Some(("c".to_string(), None)))
+        );
+    }
+
+    #[test]
+    fn test_debug_overlay_rects() {
+        let mut editor = Editor::new_headless(100, 50);
+        editor.handle_redraw_event(RedrawEvent::Resize {
+            grid: MAIN_GRID,
+            width: 10,
+            height: 3,
+        });
+        editor.build_draw_commands();
+        editor.grid.set_dirty_cell(2, 1);
+        editor.grid.set_dirty_cell(3, 1);
+        editor.grid.set_dirty_cell(9, 2);
+        assert!(editor.debug_overlay_rects().is_empty());
+
+        // RUN FUNCTION
+        editor.set_debug_overlay(true);
+
+        assert_eq!(
+            editor.debug_overlay_rects(),
+            vec![(2, 1, 2, 1), (9, 2, 1, 1)]
+        );
+    }
+
+    #[test]
+    fn test_new_headless() {
+        // RUN FUNCTION
+        let mut editor = Editor::new_headless(8, 2);
+
+        assert_eq!((editor.grid.width, editor.grid.height), (8, 2));
+        editor.apply_grid_lines(vec![(
+            MAIN_GRID,
+            1,
+            0,
+            vec![GridLineCell {
+                text: "headless".to_string(),
+                highlight_id: None,
+                repeat: None,
+            }],
+        )]);
+
+        let (draw_commands, should_clear) = editor.build_draw_commands();
+        assert!(should_clear);
+        assert_eq!(draw_commands.len(), 2);
+        assert_eq!(draw_commands[1].text, "headless");
+    }
+
+    #[test]
+    fn test_cursor_render() {
+        let mut editor = Editor::new_headless(100, 50);
+        let red = Color4f::new(1.0, 0.0, 0.0, 1.0);
+        let blue = Color4f::new(0.0, 0.0, 1.0, 1.0);
+        editor
+            .defined_styles
+            .insert(1, Arc::new(Style::new(COLORS)));
+        editor.defined_styles.insert(
+            2,
+            Arc::new(Style::new(Colors::new(
+                Some(red.clone()),
+                Some(blue.clone()),
+                None,
+            ))),
+        );
+        editor.apply_grid_lines(vec![(
+            MAIN_GRID,
+            0,
+            0,
+            vec![GridLineCell {
+                text: "a".to_string(),
+                highlight_id: Some(1),
+                repeat: None,
+            }],
+        )]);
+        let default_colors = editor.default_style.colors.clone();
+
+        // RUN FUNCTION
+        assert_eq!(
+            editor.cursor_render(),
+            (
+                CursorShape::Block,
+                COLORS.background.clone().unwrap(),
+                COLORS.foreground.clone().unwrap()
+            )
+        );
+
+        editor.cursor.position = (5, 5);
+        assert_eq!(
+            editor.cursor_render(),
+            (
+                CursorShape::Block,
+                default_colors.background.clone().unwrap(),
+                default_colors.foreground.clone().unwrap()
+            )
+        );
+
+        editor.cursor.change_mode(
+            &CursorMode {
+                shape: Some(CursorShape::Vertical),
+                style_id: Some(2),
+                ..Default::default()
+            },
+            &editor.defined_styles,
+        );
+        assert_eq!(editor.cursor_render(), (CursorShape::Vertical, red, blue));
+    }
+
+    #[test]
+    fn test_dump_styles() {
+        let mut editor = Editor::new_headless(100, 50);
+        let mut bold = Style::new(Colors::new(
+            Some(Color4f::new(1.0, 0.0, 0.0, 1.0)),
+            None,
+            None,
+        ));
+        bold.bold = true;
+        editor.defined_styles.insert(12, Arc::new(bold));
+        editor.defined_styles.insert(
+            3,
+            Arc::new(Style::new(Colors::new(
+                None,
+                Some(Color4f::new(0.0, 0.2, 1.0, 1.0)),
+                None,
+            ))),
+        );
+
+        // RUN FUNCTION
+        let dump = editor.dump_styles();
+
+        let lines: Vec<&str> = dump.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert!(lines[0].starts_with("default "));
+        assert_eq!(lines[1], "3 fg=none bg=#0033ff sp=none blend=0 flags=[]");
+        assert_eq!(
+            lines[2],
+            "12 fg=#ff0000 bg=none sp=none blend=0 flags=[bold]"
+        );
+    }
+
+    #[test]
+    fn test_cursor_cell_span() {
+        let mut editor = Editor::new_headless(100, 50);
+        let cell = |text: &str| GridLineCell {
+            text: text.to_string(),
+            highlight_id: Some(0),
+            repeat: None,
+        };
+        editor.apply_grid_lines(vec![(
+            MAIN_GRID,
+            0,
+            0,
+            vec![cell("a"), cell("一"), cell("")],
+        )]);
+
+        // RUN FUNCTION
+        editor.cursor.position = (1, 0);
+        assert_eq!(editor.cursor_cell_span(), 2);
+
+        editor.cursor.position = (2, 0);
+        assert_eq!(editor.cursor_cell_span(), 2);
+
+        editor.cursor.position = (0, 0);
+        assert_eq!(editor.cursor_cell_span(), 1);
+
+        editor.cursor.position = (3, 0);
+        assert_eq!(editor.cursor_cell_span(), 1);
+    }
+
+    #[test]
+    fn test_cursor_render_after_clear() {
+        let mut editor = Editor::new_headless(100, 50);
+        editor
+            .defined_styles
+            .insert(1, Arc::new(Style::new(COLORS)));
+        editor.apply_grid_lines(vec![(
+            MAIN_GRID,
+            5,
+            0,
+            vec![GridLineCell {
+                text: "x".to_string(),
+                highlight_id: Some(1),
+                repeat: Some(10),
+            }],
+        )]);
+        editor.cursor.position = (5, 5);
+        let default_colors = editor.default_style.colors.clone();
+
+        // RUN FUNCTION
+        editor.handle_redraw_event(RedrawEvent::Clear { grid: MAIN_GRID });
+
+        assert_eq!(
+            editor.cursor_render(),
+            (
+                CursorShape::Block,
+                default_colors.background.unwrap(),
+                default_colors.foreground.unwrap()
+            )
+        );
+        assert_eq!(editor.cursor_cell_span(), 1);
+    }
+
+    #[test]
+    fn test_mouse_move_enabled() {
+        let mut editor = Editor::new_headless(100, 50);
+        editor.handle_redraw_event(RedrawEvent::OptionSet {
+            gui_option: GuiOption::MouseMoveEvent(true),
+        });
+
+        // RUN FUNCTION
+        assert!(editor.mouse_move(3, 4));
+        assert!(!editor.mouse_move(3, 4));
+        assert!(editor.mouse_move(3, 5));
+        assert!(editor.mouse_move(4, 5));
+    }
+
+    #[test]
+    fn test_paste_stream() {
+        let editor = Editor::new_headless(100, 50);
+        let paste =
+            |chunks: &[&str]| editor.paste_stream(chunks.iter().map(|chunk| chunk.to_string()));
+
+        // RUN FUNCTION
+        assert_eq!(paste(&["hello"]), vec![("hello".to_string(), -1)]);
+        assert_eq!(
+            paste(&["a\n", "b\n", "c\n", "d"]),
+            vec![
+                ("a\n".to_string(), 1),
+                ("b\n".to_string(), 2),
+                ("c\n".to_string(), 2),
+                ("d".to_string(), 3),
+            ]
+        );
+        assert_eq!(
+            paste(&["a", "b"]),
+            vec![("a".to_string(), 1), ("b".to_string(), 3)]
+        );
+        assert!(paste(&[]).is_empty());
+    }
+    #[test]
+    fn test_mouse_move_disabled() {
+        let mut editor = Editor::new_headless(100, 50);
+
+        // RUN FUNCTION
+        assert!(!editor.mouse_move(3, 4));
+        assert!(!editor.mouse_move(3, 5));
+
+        editor.handle_redraw_event(RedrawEvent::OptionSet {
+            gui_option: GuiOption::MouseMoveEvent(true),
+        });
+        assert!(editor.mouse_move(3, 5));
+        editor.handle_redraw_event(RedrawEvent::OptionSet {
+            gui_option: GuiOption::MouseMoveEvent(false),
+        });
+        assert!(!editor.mouse_move(3, 6));
+    }
+
+    #[test]
+    fn test_clearing_run_empties_stale_tail() {
+        let mut editor = Editor::new_headless(100, 50);
+        editor
+            .defined_styles
+            .insert(1, Arc::new(Style::new(COLORS)));
+        editor.apply_grid_lines(vec![(
+            MAIN_GRID,
+            0,
+            0,
+            vec![GridLineCell {
+                text: "hello world".to_string(),
+                highlight_id: Some(1),
+                repeat: None,
+            }],
+        )]);
+
+        // RUN FUNCTION
+        editor.apply_grid_lines(vec![(
+            MAIN_GRID,
+            0,
+            0,
+            vec![
+                GridLineCell {
+                    text: "hi".to_string(),
+                    highlight_id: Some(1),
+                    repeat: None,
+                },
+                GridLineCell {
+                    text: " ".to_string(),
+                    highlight_id: Some(0),
+                    repeat: Some(9),
+                },
+            ],
+        )]);
+
+        assert_eq!(editor.cursor_line_text(), "hi");
+        for x in 2..11 {
+            assert_eq!(editor.grid.get_cell(x, 0), Some(&None));
+        }
+    }
+
+    #[test]
+    fn test_background_thumbnail() {
+        let mut editor = Editor::new_headless(3, 2);
+        let red = Color4f::new(1.0, 0.0, 0.0, 1.0);
+        let blue = Color4f::new(0.0, 0.0, 1.0, 1.0);
+        editor.defined_styles.insert(
+            1,
+            Arc::new(Style::new(Colors::new(None, Some(red.clone()), None))),
+        );
+        editor.defined_styles.insert(
+            2,
+            Arc::new(Style::new(Colors::new(None, Some(blue.clone()), None))),
+        );
+        editor.apply_grid_lines(vec![
+            (
+                MAIN_GRID,
+                0,
+                1,
+                vec![GridLineCell {
+                    text: "a".to_string(),
+                    highlight_id: Some(1),
+                    repeat: None,
+                }],
+            ),
+            (
+                MAIN_GRID,
+                1,
+                2,
+                vec![GridLineCell {
+                    text: "b".to_string(),
+                    highlight_id: Some(2),
+                    repeat: None,
+                }],
+            ),
+        ]);
+        let background = editor.default_style.colors.background.clone().unwrap();
+
+        // RUN FUNCTION
+        assert_eq!(
+            editor.background_thumbnail(),
+            vec![
+                vec![background.clone(), red, background.clone()],
+                vec![background.clone(), background, blue],
+            ]
+        );
+    }
+
+    #[test]
+    fn test_set_ligatures_enabled() {
+        let mut editor = Editor::new_headless(10, 1);
+        editor
+            .defined_styles
+            .insert(1, Arc::new(Style::new(COLORS)));
+        editor.apply_grid_lines(vec![(
+            MAIN_GRID,
+            0,
+            0,
+            vec![GridLineCell {
+                text: "->=".to_string(),
+                highlight_id: Some(1),
+                repeat: None,
+            }],
+        )]);
+        assert!(editor.ligatures_enabled());
+        assert_eq!(editor.draw_command_count(), 2);
+
+        // RUN FUNCTION
+        editor.set_ligatures_enabled(false);
+        assert!(!editor.ligatures_enabled());
+        let (draw_commands, _) = editor.build_draw_commands();
+        let texts: Vec<&str> = draw_commands
+            .iter()
+            .map(|command| command.text.as_str())
+            .collect();
+        assert_eq!(texts, vec!["-", ">", "=", "       "]);
+        assert_eq!(editor.draw_command_count(), 4);
+
+        editor.set_ligatures_enabled(true);
+        assert!(editor.ligatures_enabled());
+        assert_eq!(editor.draw_command_count(), 2);
+    }
+
+    #[test]
+    fn test_split_by_script() {
+        let style = Some(Arc::new(Style::new(COLORS)));
+        let command = DrawCommand::new("ab 中文 cd".to_string(), 9, (2, 3), style.clone());
+
+        // RUN FUNCTION
+        let commands = command.split_by_script();
+        let pieces: Vec<(&str, u64, (u64, u64))> = commands
+            .iter()
+            .map(|command| {
+                (
+                    command.text.as_str(),
+                    command.cell_width,
+                    command.grid_position,
+                )
+            })
+            .collect();
+        assert_eq!(
+            pieces,
+            vec![("ab ", 3, (2, 3)), ("中文 ", 3, (5, 3)), ("cd", 2, (8, 3))]
+        );
+        assert!(commands.iter().all(|command| command.style == style));
+
+        let latin = DrawCommand::new("hello".to_string(), 5, (0, 0), None);
+        assert_eq!(latin.split_by_script().len(), 1);
+    }
+
+    #[test]
+    fn test_row_unchanged_since() {
+        let mut editor = Editor::new_headless(5, 2);
+        editor.apply_grid_lines(vec![(
+            MAIN_GRID,
+            0,
+            0,
+            vec![GridLineCell {
+                text: "abc".to_string(),
+                highlight_id: None,
+                repeat: None,
+            }],
+        )]);
+        let snapshot: Vec<Vec<GridCell>> = editor.grid.rows().map(|row| row.to_vec()).collect();
+
+        // RUN FUNCTION
+        assert!(editor.row_unchanged_since(0, &snapshot));
+        assert!(editor.row_unchanged_since(1, &snapshot));
+
+        editor.apply_grid_lines(vec![(
+            MAIN_GRID,
+            0,
+            1,
+            vec![GridLineCell {
+                text: "x".to_string(),
+                highlight_id: None,
+                repeat: None,
+            }],
+        )]);
+        assert!(!editor.row_unchanged_since(0, &snapshot));
+        assert!(editor.row_unchanged_since(1, &snapshot));
+
+        let mut shorter = snapshot.clone();
+        shorter[1].pop();
+        assert!(!editor.row_unchanged_since(1, &shorter));
+        assert!(!editor.row_unchanged_since(2, &snapshot));
+    }
+
+    #[test]
+    fn test_set_working_dir() {
+        let mut editor = Editor::new_headless(100, 50);
+        assert_eq!(editor.working_dir(), None);
+
+        // RUN FUNCTION
+        editor.set_working_dir("/home/user/project".to_string());
+        assert_eq!(editor.working_dir(), Some("/home/user/project"));
+
+        editor.set_working_dir("/tmp".to_string());
+        assert_eq!(editor.working_dir(), Some("/tmp"));
+    }
+
+    #[test]
+    fn test_content_rows() {
+        let mut editor = Editor::new_headless(10, 6);
+        assert_eq!(editor.content_rows(), 0);
+
+        let line = |row| {
+            (
+                MAIN_GRID,
+                row,
+                0,
+                vec![GridLineCell {
+                    text: "abc".to_string(),
+                    highlight_id: None,
+                    repeat: None,
+                }],
+            )
+        };
+
+        // RUN FUNCTION
+        editor.apply_grid_lines(vec![line(0), line(1), line(2)]);
+        assert_eq!(editor.content_rows(), 3);
+
+        editor.apply_grid_lines(vec![line(5)]);
+        assert_eq!(editor.content_rows(), 6);
+    }
+
+    #[test]
+    fn test_astral_character_takes_two_cells() {
+        let mut editor = Editor::new_headless(10, 1);
+
+        // RUN FUNCTION
+        editor.apply_grid_lines(vec![(
+            MAIN_GRID,
+            0,
+            0,
+            vec![
+                GridLineCell {
+                    text: "\u{1f600}".to_string(),
+                    highlight_id: None,
+                    repeat: None,
+                },
+                GridLineCell {
+                    text: "".to_string(),
+                    highlight_id: None,
+                    repeat: None,
+                },
+                GridLineCell {
+                    text: "a".to_string(),
+                    highlight_id: None,
+                    repeat: None,
+                },
+            ],
+        )]);
+
+        assert_eq!(
+            editor.grid.get_cell(0, 0),
+            Some(&Some(("\u{1f600}".to_string(), None)))
+        );
+        assert_eq!(
+            editor.grid.get_cell(1, 0),
+            Some(&Some(("".to_string(), None)))
+        );
+        assert_eq!(
+            editor.grid.get_cell(2, 0),
+            Some(&Some(("a".to_string(), None)))
+        );
+        assert_eq!(editor.cursor_cell_span(), 2);
+
+        let (draw_commands, _) = editor.build_draw_commands();
+        assert_eq!(draw_commands[0].text, "\u{1f600} ");
+        assert_eq!(draw_commands[0].cell_width, 2);
+        assert_eq!(draw_commands[1].grid_position, (2, 0));
+    }
+
+    #[test]
+    fn test_reset() {
+        let mut editor = Editor::new_headless(20, 10);
+        editor
+            .defined_styles
+            .insert(1, Arc::new(Style::new(COLORS)));
+        editor.handle_redraw_event(RedrawEvent::DefaultColorsSet {
+            colors: COLORS.clone(),
+        });
+        editor.apply_grid_lines(vec![(
+            MAIN_GRID,
+            0,
+            0,
+            vec![GridLineCell {
+                text: "hello".to_string(),
+                highlight_id: Some(1),
+                repeat: None,
+            }],
+        )]);
+        editor.handle_redraw_event(RedrawEvent::Resize {
+            grid: 2,
+            width: 5,
+            height: 5,
+        });
+        editor.handle_redraw_event(RedrawEvent::WindowPosition {
+            grid: 2,
+            window: 0,
+            start_row: 1,
+            start_column: 1,
+            width: 5,
+            height: 5,
+        });
+
+        // RUN FUNCTION
+        editor.reset();
+
+        assert_eq!((editor.grid.width, editor.grid.height), (20, 10));
+        assert!(editor
+            .grid
+            .rows()
+            .all(|row| row.iter().all(|cell| cell.is_none())));
+        assert!(editor.defined_styles.is_empty());
+        assert_eq!(editor.previous_style, None);
+        assert!(editor.grids.is_empty());
+        assert!(editor.grid_positions.is_empty());
+        assert!(editor.grid_order.is_empty());
+        assert_eq!(editor.focused_grid, MAIN_GRID);
+        assert_eq!(editor.default_style, initial_default_style());
+    }
+
+    #[test]
+    fn test_grid_blend() {
+        let mut editor = Editor::new_headless(100, 50);
+
+        // RUN FUNCTION
+        editor.set_grid_blend(4, 30);
+        assert_eq!(editor.grid_blend(4), 30);
+        assert_eq!(editor.grid_blend(5), 0);
+
+        editor.set_grid_blend(4, 150);
+        assert_eq!(editor.grid_blend(4), 100);
+    }
+
+    #[test]
+    fn test_composite_grid_background_uses_grid_blend() {
+        let mut editor = Editor::new_headless(10, 10);
+        editor.handle_redraw_event(RedrawEvent::Resize {
+            grid: 4,
+            width: 2,
+            height: 2,
+        });
+        let white = Color4f::new(1.0, 1.0, 1.0, 1.0);
+        editor.set_grid_background(4, Some(white.clone()));
+        assert_eq!(editor.composite_grid_background(4, 0, 0), white);
+
+        // RUN FUNCTION
+        editor.set_grid_blend(4, 50);
+        assert_eq!(
+            editor.composite_grid_background(4, 0, 0),
+            Color4f::new(0.5, 0.5, 0.5, 1.0)
+        );
+    }
+
+    #[test]
+    fn test_row_is_blank() {
+        let mut editor = Editor::new_headless(5, 4);
+        editor
+            .defined_styles
+            .insert(1, Arc::new(Style::new(COLORS)));
+        let line = |row, text: &str, highlight_id| {
+            (
+                MAIN_GRID,
+                row,
+                0,
+                vec![GridLineCell {
+                    text: text.to_string(),
+                    highlight_id: Some(highlight_id),
+                    repeat: None,
+                }],
+            )
+        };
+        editor.apply_grid_lines(vec![
+            line(1, "     ", 0),
+            line(2, "  ", 0),
+            line(3, "ab", 0),
+        ]);
+        editor.apply_grid_lines(vec![(
+            MAIN_GRID,
+            2,
+            2,
+            vec![GridLineCell {
+                text: " ".to_string(),
+                highlight_id: Some(1),
+                repeat: None,
+            }],
+        )]);
+
+        // RUN FUNCTION
+        assert!(editor.row_is_blank(0));
+        assert!(editor.row_is_blank(1));
+        assert!(!editor.row_is_blank(2));
+        assert!(!editor.row_is_blank(3));
+    }
+
+    #[test]
+    fn test_attach() {
+        let mut editor = Editor::new_headless(80, 24);
+        let options = AttachOptions::new(true, true, false, true, false);
+
+        // RUN FUNCTION
+        let arguments = editor.attach(options.clone());
+        assert_eq!(
+            arguments,
+            vec![
+                Value::from(80),
+                Value::from(24),
+                Value::Map(vec![
+                    (Value::from("rgb"), Value::from(true)),
+                    (Value::from("ext_linegrid"), Value::from(true)),
+                    (Value::from("ext_multigrid"), Value::from(true)),
+                    (Value::from("ext_popupmenu"), Value::from(false)),
+                    (Value::from("ext_cmdline"), Value::from(true)),
+                    (Value::from("ext_messages"), Value::from(false)),
+                ]),
+            ]
+        );
+        assert_eq!(editor.attach_options, options);
+    }
+
+    #[test]
+    fn test_cursor_local_position() {
+        let mut editor = Editor::new_headless(100, 50);
+        assert_eq!(editor.cursor_local_position(), (MAIN_GRID, 0, 0));
+
+        // RUN FUNCTION
+        editor.set_cursor_position(2, (7, 3));
+        assert_eq!(editor.cursor_local_position(), (2, 3, 7));
+    }
+
+    #[test]
+    fn test_draw_command_decorations() {
+        let mut style = Style::new(COLORS);
+        style.underline = true;
+        style.strikethrough = true;
+        let decorated = DrawCommand::new("a".to_string(), 1, (0, 0), Some(Arc::new(style)));
+        let plain = DrawCommand::new("a".to_string(), 1, (0, 0), None);
+
+        // RUN FUNCTION
+        let decorations = decorated.decorations();
+        assert_eq!(decorations.underline, UnderlineStyle::Single);
+        assert!(decorations.strikethrough);
+        assert!(plain.decorations().is_empty());
+    }
+
+    #[test]
+    fn test_dirty_spans() {
+        let mut editor = Editor::new_headless(4, 8);
+        assert_eq!(editor.dirty_spans(), vec![(0, 8)]);
+        editor.grid.set_dirty_all(false);
+
+        // RUN FUNCTION
+        editor.grid.set_dirty_cell(3, 1);
+        editor.grid.set_dirty_cell(0, 2);
+        editor.grid.set_dirty_cell(2, 5);
+        assert_eq!(editor.dirty_spans(), vec![(1, 3), (5, 6)]);
+
+        editor.grid.set_dirty_all(false);
+        assert_eq!(editor.dirty_spans(), vec![]);
+    }
+
+    fn large_grid_line_batch() -> Vec<(u64, u64, u64, Vec<GridLineCell>)> {
+        (0..3)
+            .map(|row| {
+                (
+                    MAIN_GRID,
+                    row,
+                    1,
+                    vec![
+                        GridLineCell {
+                            text: "ab".to_string(),
+                            highlight_id: Some(1),
+                            repeat: None,
+                        },
+                        GridLineCell {
+                            text: "c".to_string(),
+                            highlight_id: None,
+                            repeat: Some(3),
+                        },
+                        GridLineCell {
+                            text: " ".to_string(),
+                            highlight_id: Some(0),
+                            repeat: Some(2),
+                        },
+                    ],
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_apply_grid_lines_chunked() {
+        let style = Arc::new(Style::new(COLORS));
+        let mut expected = Editor::new_headless(10, 4);
+        expected.defined_styles.insert(1, style.clone());
+        expected.apply_grid_lines(large_grid_line_batch());
+        let mut editor = Editor::new_headless(10, 4);
+        editor.defined_styles.insert(1, style);
+
+        // RUN FUNCTION
+        assert!(!editor.apply_grid_lines_chunked(large_grid_line_batch(), 4));
+        assert!(editor
+            .grid
+            .rows()
+            .nth(2)
+            .unwrap()
+            .iter()
+            .all(|cell| cell.is_none()));
+        assert!(editor.apply_grid_lines_chunked(Vec::new(), 100));
+
+        let rows: Vec<Vec<GridCell>> = editor.grid.rows().map(|row| row.to_vec()).collect();
+        let expected_rows: Vec<Vec<GridCell>> =
+            expected.grid.rows().map(|row| row.to_vec()).collect();
+        assert_eq!(rows, expected_rows);
+        assert!(editor.pending_grid_lines.is_empty());
+        assert_eq!(editor.pending_line_column, None);
+    }
+
+    #[test]
+    fn test_previous_cursor_cells() {
+        let mut editor = Editor::new_headless(10, 2);
+        editor.apply_grid_lines(vec![(
+            MAIN_GRID,
+            1,
+            4,
+            vec![
+                GridLineCell {
+                    text: "\u{4e2d}".to_string(),
+                    highlight_id: None,
+                    repeat: None,
+                },
+                GridLineCell {
+                    text: "".to_string(),
+                    highlight_id: None,
+                    repeat: None,
+                },
+            ],
+        )]);
+        assert_eq!(editor.previous_cursor_cells(), vec![]);
+
+        // RUN FUNCTION
+        editor.set_cursor_position(MAIN_GRID, (2, 0));
+        editor.set_cursor_position(MAIN_GRID, (5, 1));
+        assert_eq!(editor.previous_cursor_cells(), vec![(2, 0)]);
+
+        editor.set_cursor_position(MAIN_GRID, (0, 0));
+        assert_eq!(editor.previous_cursor_cells(), vec![(4, 1), (5, 1)]);
+    }
+
+    #[test]
+    fn test_guifont_wide() {
+        let mut editor = Editor::new_headless(100, 50);
+        assert_eq!(editor.guifont_wide(), None);
+
+        // RUN FUNCTION
+        editor.handle_redraw_event(RedrawEvent::OptionSet {
+            gui_option: GuiOption::GuiFontWide("Noto Sans CJK:h14".to_string()),
+        });
+        assert_eq!(
+            editor.guifont_wide(),
+            Some(("Noto Sans CJK".to_string(), Some(14.0)))
+        );
+
+        editor.handle_redraw_event(RedrawEvent::OptionSet {
+            gui_option: GuiOption::GuiFontWide("Source Han Sans".to_string()),
+        });
+        assert_eq!(
+            editor.guifont_wide(),
+            Some(("Source Han Sans".to_string(), None))
+        );
+    }
+
+    #[test]
+    fn test_total_bounds() {
+        let mut editor = Editor::new_headless(80, 24);
+        assert_eq!(editor.total_bounds(), (80, 24));
+
+        // RUN FUNCTION
+        open_float(&mut editor, 2, (10, 5), (20, 10));
+        assert_eq!(editor.total_bounds(), (80, 24));
+
+        open_float(&mut editor, 3, (70, 20), (20, 10));
+        assert_eq!(editor.total_bounds(), (90, 30));
+    }
+
+    #[test]
+    fn test_url_at() {
+        let mut editor = Editor::new_headless(20, 2);
+        let mut style = Style::new(COLORS);
+        style.url = Some("https://neovim.io".to_string());
+        editor.defined_styles.insert(1, Arc::new(style));
+        editor.apply_grid_lines(vec![(
+            MAIN_GRID,
+            1,
+            2,
+            vec![
+                GridLineCell {
+                    text: "neovim".to_string(),
+                    highlight_id: Some(1),
+                    repeat: None,
+                },
+                GridLineCell {
+                    text: " text".to_string(),
+                    highlight_id: Some(0),
+                    repeat: None,
+                },
+            ],
+        )]);
+
+        // RUN FUNCTION
+        assert_eq!(editor.url_at(1, 2), Some("https://neovim.io"));
+        assert_eq!(editor.url_at(1, 7), Some("https://neovim.io"));
+        assert_eq!(editor.url_at(1, 8), None);
+        assert_eq!(editor.url_at(0, 2), None);
+
+        let (draw_commands, _) = editor.build_draw_commands();
+        let link = draw_commands
+            .iter()
+            .find(|command| command.text == "neovim")
+            .unwrap();
+        assert_eq!(link.url(), Some("https://neovim.io"));
+    }
+
+    #[test]
+    fn test_handle_url_click() {
+        let mut editor = Editor::new_headless(20, 1);
+        let mut style = Style::new(COLORS);
+        style.url = Some("https://github.com/neovim/neovim".to_string());
+        editor.defined_styles.insert(1, Arc::new(style));
+        editor.apply_grid_lines(vec![(
+            MAIN_GRID,
+            0,
+            0,
+            vec![
+                GridLineCell {
+                    text: "see ".to_string(),
+                    highlight_id: Some(0),
+                    repeat: None,
+                },
+                GridLineCell {
+                    text: "neovim".to_string(),
+                    highlight_id: Some(1),
+                    repeat: None,
+                },
+            ],
+        )]);
+
+        // RUN FUNCTION
+        assert_eq!(
+            editor.handle_url_click(0, 6),
+            Some("https://github.com/neovim/neovim".to_string())
+        );
+        assert_eq!(editor.handle_url_click(0, 1), None);
+        assert_eq!(editor.handle_url_click(0, 12), None);
+    }
+
+    #[test]
+    fn test_wrap_text() {
+        let editor = Editor::new_headless(100, 50);
+
+        // RUN FUNCTION
+        assert_eq!(
+            editor.wrap_text("hello world", 4),
+            vec!["hell", "o wo", "rld"]
+        );
+        assert_eq!(
+            editor.wrap_text("ab\u{4e2d}\u{6587}", 3),
+            vec!["ab", "\u{4e2d}", "\u{6587}"]
+        );
+        assert_eq!(editor.wrap_text("a\u{4e2d}b", 3), vec!["a\u{4e2d}", "b"]);
+        assert_eq!(editor.wrap_text("one\ntwo", 10), vec!["one", "two"]);
+        assert_eq!(editor.wrap_text("", 10), Vec::<String>::new());
+    }
+
+    #[test]
+    fn test_scroll_ease() {
+        let mut editor = Editor::new_headless(100, 50);
+        assert_eq!(editor.scroll_easing, EasingKind::OutCubic);
+
+        // RUN FUNCTION
+        assert_eq!(editor.scroll_ease(0.0), 0.0);
+        assert_eq!(editor.scroll_ease(1.0), 1.0);
+        assert!(editor.scroll_ease(0.5) > 0.5);
+        let samples: Vec<f32> = (0..=20)
+            .map(|step| editor.scroll_ease(step as f32 / 20.0))
+            .collect();
+        assert!(samples.windows(2).all(|pair| pair[0] < pair[1]));
+        assert_eq!(editor.scroll_ease(2.0), 1.0);
+
+        editor.set_scroll_easing(EasingKind::Linear);
+        assert_eq!(editor.scroll_ease(0.25), 0.25);
+    }
+
+    #[test]
+    fn test_combining_mark_joins_previous_cell() {
+        let mut editor = Editor::new_headless(10, 1);
+
+        // RUN FUNCTION
+        editor.apply_grid_lines(vec![(
+            MAIN_GRID,
+            0,
+            0,
+            vec![
+                GridLineCell {
+                    text: "e".to_string(),
+                    highlight_id: None,
+                    repeat: None,
+                },
+                GridLineCell {
+                    text: "\u{301}".to_string(),
+                    highlight_id: None,
+                    repeat: None,
+                },
+            ],
+        )]);
+
+        assert_eq!(
+            editor.grid.get_cell(0, 0),
+            Some(&Some(("e\u{301}".to_string(), None)))
+        );
+        assert_eq!(editor.grid.get_cell(1, 0), Some(&None));
+    }
+
+    fn show_message(editor: &mut Editor, text: &str, replace_last: bool) {
+        editor.handle_redraw_event(RedrawEvent::MessageShow {
+            kind: crate::bridge::MessageKind::Echo,
+            content: vec![(1, text.to_string())],
+            replace_last,
+        });
+    }
+
+    #[test]
+    fn test_message_history() {
+        let mut editor = Editor::new_headless(100, 50);
+        let style = Style::new(COLORS);
+        editor.defined_styles.insert(1, Arc::new(style.clone()));
+        editor.set_message_history_capacity(3);
+
+        // RUN FUNCTION
+        for text in &["one", "two", "three", "four", "five"] {
+            show_message(&mut editor, text, false);
+        }
+        assert_eq!(
+            editor.message_history(10),
+            vec![
+                (style.clone(), "three".to_string()),
+                (style.clone(), "four".to_string()),
+                (style.clone(), "five".to_string()),
+            ]
+        );
+        assert_eq!(
+            editor.message_history(1),
+            vec![(style.clone(), "five".to_string())]
+        );
+
+        show_message(&mut editor, "six", true);
+        let texts: Vec<String> = editor
+            .message_history(10)
+            .into_iter()
+            .map(|(_, text)| text)
+            .collect();
+        assert_eq!(texts, vec!["three", "four", "six"]);
+    }
 }
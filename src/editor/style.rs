@@ -1,3 +1,6 @@
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+
 use skulpin::skia_safe::Color4f;
 
 #[derive(new, PartialEq, Debug, Clone)]
@@ -7,6 +10,186 @@ pub struct Colors {
     pub special: Option<Color4f>,
 }
 
+fn hash_color<H: Hasher>(color: &Option<Color4f>, state: &mut H) {
+    color
+        .as_ref()
+        .map(|color| {
+            [
+                color.r.to_bits(),
+                color.g.to_bits(),
+                color.b.to_bits(),
+                color.a.to_bits(),
+            ]
+        })
+        .hash(state);
+}
+
+impl Hash for Colors {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        hash_color(&self.foreground, state);
+        hash_color(&self.background, state);
+        hash_color(&self.special, state);
+    }
+}
+
+// Multipliers applied to every drawn color. A saturation of 0 renders in grayscale
+#[derive(new, Debug, Clone, PartialEq)]
+pub struct ColorFilter {
+    pub saturation: f32,
+    pub brightness: f32,
+}
+
+impl Default for ColorFilter {
+    fn default() -> Self {
+        ColorFilter::new(1.0, 1.0)
+    }
+}
+
+impl ColorFilter {
+    pub fn is_identity(&self) -> bool {
+        *self == ColorFilter::default()
+    }
+
+    fn apply(&self, color: &Color4f) -> Color4f {
+        let luminance = 0.2126 * color.r + 0.7152 * color.g + 0.0722 * color.b;
+        let filter_channel = |channel: f32| {
+            ((luminance + (channel - luminance) * self.saturation) * self.brightness)
+                .clamp(0.0, 1.0)
+        };
+        Color4f::new(
+            filter_channel(color.r),
+            filter_channel(color.g),
+            filter_channel(color.b),
+            color.a,
+        )
+    }
+}
+
+impl Colors {
+    // Snaps every channel to the nearest of `levels` evenly spaced steps between 0 and 1
+    pub fn quantize(&self, levels: u8) -> Colors {
+        let steps = f32::from(levels.max(2) - 1);
+        let quantize_color = |color: &Color4f| {
+            let quantize_channel = |channel: f32| (channel.clamp(0.0, 1.0) * steps).round() / steps;
+            Color4f::new(
+                quantize_channel(color.r),
+                quantize_channel(color.g),
+                quantize_channel(color.b),
+                color.a,
+            )
+        };
+        Colors::new(
+            self.foreground.as_ref().map(quantize_color),
+            self.background.as_ref().map(quantize_color),
+            self.special.as_ref().map(quantize_color),
+        )
+    }
+
+    pub fn apply_filter(&self, filter: &ColorFilter) -> Colors {
+        Colors::new(
+            self.foreground.as_ref().map(|color| filter.apply(color)),
+            self.background.as_ref().map(|color| filter.apply(color)),
+            self.special.as_ref().map(|color| filter.apply(color)),
+        )
+    }
+
+    // Moves the foreground towards white or black, whichever contrasts more with the background,
+    // until the WCAG contrast ratio between them reaches `min_contrast`
+    #[allow(dead_code)]
+    pub fn legible_foreground(&self, min_contrast: f32) -> Option<Color4f> {
+        let foreground = self.foreground.as_ref()?;
+        let background = self.background.as_ref()?;
+
+        if contrast_ratio(foreground, background) >= min_contrast {
+            return Some(foreground.clone());
+        }
+
+        let white = Color4f::new(1.0, 1.0, 1.0, foreground.a);
+        let black = Color4f::new(0.0, 0.0, 0.0, foreground.a);
+        let target = if contrast_ratio(&white, background) >= contrast_ratio(&black, background) {
+            white
+        } else {
+            black
+        };
+
+        // blend_color treats its value as the amount of `target` mixed in
+        Some(
+            (1..=100)
+                .map(|amount| blend_color(foreground, &target, amount))
+                .find(|candidate| contrast_ratio(candidate, background) >= min_contrast)
+                .unwrap_or(target),
+        )
+    }
+}
+
+// Relative luminance as defined by WCAG 2.0, from linearized sRGB channels
+fn relative_luminance(color: &Color4f) -> f32 {
+    let linearize = |channel: f32| {
+        let channel = channel.clamp(0.0, 1.0);
+        if channel <= 0.03928 {
+            channel / 12.92
+        } else {
+            ((channel + 0.055) / 1.055).powf(2.4)
+        }
+    };
+    0.2126 * linearize(color.r) + 0.7152 * linearize(color.g) + 0.0722 * linearize(color.b)
+}
+
+// Ranges from 1 for identical colors up to 21 for black on white
+fn contrast_ratio(first: &Color4f, second: &Color4f) -> f32 {
+    let first = relative_luminance(first);
+    let second = relative_luminance(second);
+    (first.max(second) + 0.05) / (first.min(second) + 0.05)
+}
+
+// Neovim blend values range from 0 (opaque) to 100 (fully transparent)
+pub fn blend_color(top: &Color4f, bottom: &Color4f, blend: u8) -> Color4f {
+    let t = f32::from(blend.min(100)) / 100.0;
+    Color4f::new(
+        top.r + (bottom.r - top.r) * t,
+        top.g + (bottom.g - top.g) * t,
+        top.b + (bottom.b - top.b) * t,
+        top.a + (bottom.a - top.a) * t,
+    )
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum UnderlineStyle {
+    #[default]
+    None,
+    Single,
+    Double,
+    Dotted,
+    Dashed,
+    Curl,
+}
+
+impl UnderlineStyle {
+    pub fn from_flags(underline: bool, undercurl: bool) -> UnderlineStyle {
+        if undercurl {
+            UnderlineStyle::Curl
+        } else if underline {
+            UnderlineStyle::Single
+        } else {
+            UnderlineStyle::None
+        }
+    }
+}
+
+// Every line a style draws over its text, so the renderer can handle them in one place
+#[derive(new, Debug, Clone, Copy, PartialEq, Default)]
+pub struct DecorationSet {
+    pub underline: UnderlineStyle,
+    pub strikethrough: bool,
+}
+
+impl DecorationSet {
+    #[allow(dead_code)]
+    pub fn is_empty(&self) -> bool {
+        self.underline == UnderlineStyle::None && !self.strikethrough
+    }
+}
+
 #[derive(new, Debug, Clone, PartialEq)]
 pub struct Style {
     pub colors: Colors,
@@ -23,10 +206,116 @@ pub struct Style {
     #[new(default)]
     pub undercurl: bool,
     #[new(default)]
+    pub underline_style: UnderlineStyle,
+    #[new(default)]
     pub blend: u8,
+    #[new(default)]
+    pub url: Option<String>,
+}
+
+// Concrete colors and decorations ready to draw, with reverse and blend already applied
+#[derive(Debug, Clone, PartialEq)]
+pub struct ResolvedStyle {
+    pub foreground: Color4f,
+    pub background: Color4f,
+    pub special: Color4f,
+    pub bold: bool,
+    pub italic: bool,
+    pub underline: bool,
+    pub undercurl: bool,
+    pub underline_style: UnderlineStyle,
+    pub strikethrough: bool,
+}
+
+// Colors come from Neovim as integers, so they are never NaN or negative zero and
+// comparing them is a total equality consistent with their bit patterns
+impl Eq for Style {}
+
+impl Hash for Style {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.colors.hash(state);
+        self.reverse.hash(state);
+        self.italic.hash(state);
+        self.bold.hash(state);
+        self.strikethrough.hash(state);
+        self.underline.hash(state);
+        self.undercurl.hash(state);
+        self.underline_style.hash(state);
+        self.blend.hash(state);
+        self.url.hash(state);
+    }
 }
 
 impl Style {
+    // Compact key for glyph caches. Colors are quantized to the 8 bit channels Neovim sends
+    #[allow(dead_code)]
+    pub fn cache_key(&self) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        for color in &[
+            &self.colors.foreground,
+            &self.colors.background,
+            &self.colors.special,
+        ] {
+            color
+                .as_ref()
+                .map(|color| {
+                    [color.r, color.g, color.b, color.a]
+                        .iter()
+                        .map(|component| (component.clamp(0.0, 1.0) * 255.0).round() as u8)
+                        .collect::<Vec<u8>>()
+                })
+                .hash(&mut hasher);
+        }
+        (
+            self.reverse,
+            self.italic,
+            self.bold,
+            self.strikethrough,
+            self.underline,
+            self.undercurl,
+            self.underline_style,
+            self.blend,
+        )
+            .hash(&mut hasher);
+        hasher.finish()
+    }
+
+    // Styles which only set the underline and undercurl flags still get a matching line style
+    pub fn effective_underline_style(&self) -> UnderlineStyle {
+        match self.underline_style {
+            UnderlineStyle::None => UnderlineStyle::from_flags(self.underline, self.undercurl),
+            underline_style => underline_style,
+        }
+    }
+
+    pub fn decorations(&self) -> DecorationSet {
+        DecorationSet::new(self.effective_underline_style(), self.strikethrough)
+    }
+
+    #[allow(dead_code)]
+    pub fn resolve(&self, default_colors: &Colors) -> ResolvedStyle {
+        let mut background = self.background(default_colors);
+        if self.blend > 0 {
+            background = blend_color(
+                &background,
+                &default_colors.background.clone().unwrap(),
+                self.blend,
+            );
+        }
+
+        ResolvedStyle {
+            foreground: self.foreground(default_colors),
+            background,
+            special: self.special(default_colors),
+            bold: self.bold,
+            italic: self.italic,
+            underline: self.underline,
+            undercurl: self.undercurl,
+            underline_style: self.effective_underline_style(),
+            strikethrough: self.strikethrough,
+        }
+    }
+
     pub fn foreground(&self, default_colors: &Colors) -> Color4f {
         if self.reverse {
             self.colors
@@ -79,6 +368,29 @@ mod tests {
         special: Some(Color4f::new(0.3, 0.2, 0.1, 0.1)),
     };
 
+    #[test]
+    fn test_blend_color() {
+        let top = Color4f::new(1.0, 0.0, 0.0, 1.0);
+        let bottom = Color4f::new(0.0, 0.0, 1.0, 1.0);
+
+        assert_eq!(blend_color(&top, &bottom, 0), top);
+        assert_eq!(blend_color(&top, &bottom, 100), bottom);
+        assert_eq!(
+            blend_color(&top, &bottom, 50),
+            Color4f::new(0.5, 0.0, 0.5, 1.0)
+        );
+    }
+
+    #[test]
+    fn test_cache_key() {
+        let style = Style::new(COLORS);
+        let mut italic = Style::new(COLORS);
+        italic.italic = true;
+
+        assert_eq!(style.cache_key(), Style::new(COLORS).cache_key());
+        assert_ne!(style.cache_key(), italic.cache_key());
+    }
+
     #[test]
     fn test_foreground() {
         let mut style = Style::new(COLORS);
@@ -155,4 +467,106 @@ mod tests {
             DEFAULT_COLORS.special.clone().unwrap()
         );
     }
+
+    #[test]
+    fn test_effective_underline_style() {
+        let mut style = Style::new(COLORS);
+        assert_eq!(style.effective_underline_style(), UnderlineStyle::None);
+
+        style.underline = true;
+        assert_eq!(style.effective_underline_style(), UnderlineStyle::Single);
+
+        style.undercurl = true;
+        assert_eq!(style.effective_underline_style(), UnderlineStyle::Curl);
+
+        style.underline_style = UnderlineStyle::Dotted;
+        assert_eq!(style.effective_underline_style(), UnderlineStyle::Dotted);
+    }
+
+    #[test]
+    fn test_apply_filter() {
+        let colors = Colors::new(
+            Some(Color4f::new(1.0, 0.0, 0.0, 1.0)),
+            Some(Color4f::new(0.0, 0.0, 1.0, 0.5)),
+            None,
+        );
+
+        let grayscale = colors.apply_filter(&ColorFilter::new(0.0, 1.0));
+        let foreground = grayscale.foreground.unwrap();
+        let background = grayscale.background.unwrap();
+        assert_eq!(foreground, Color4f::new(0.2126, 0.2126, 0.2126, 1.0));
+        assert_eq!(background, Color4f::new(0.0722, 0.0722, 0.0722, 0.5));
+        assert_eq!(grayscale.special, None);
+
+        assert_eq!(colors.apply_filter(&ColorFilter::default()), colors);
+    }
+
+    #[test]
+    fn test_quantize() {
+        let colors = Colors::new(
+            Some(Color4f::new(0.2, 0.6, 0.9, 0.5)),
+            Some(Color4f::new(0.4, 0.5, 0.1, 1.0)),
+            None,
+        );
+
+        let two_levels = colors.quantize(2);
+        assert_eq!(
+            two_levels.foreground,
+            Some(Color4f::new(0.0, 1.0, 1.0, 0.5))
+        );
+        assert_eq!(
+            two_levels.background,
+            Some(Color4f::new(0.0, 1.0, 0.0, 1.0))
+        );
+        assert_eq!(two_levels.special, None);
+
+        let step = 1.0 / 3.0;
+        let four_levels = colors.quantize(4);
+        assert_eq!(
+            four_levels.foreground,
+            Some(Color4f::new(step, 2.0 * step, 1.0, 0.5))
+        );
+        assert_eq!(four_levels.special, None);
+    }
+
+    #[test]
+    fn test_legible_foreground() {
+        let low_contrast = Colors::new(
+            Some(Color4f::new(0.3, 0.3, 0.3, 1.0)),
+            Some(Color4f::new(0.2, 0.2, 0.2, 1.0)),
+            None,
+        );
+        let high_contrast = Colors::new(
+            Some(Color4f::new(0.9, 0.9, 0.9, 1.0)),
+            Some(Color4f::new(0.1, 0.1, 0.1, 1.0)),
+            None,
+        );
+
+        // RUN FUNCTION
+        let adjusted = low_contrast.legible_foreground(4.5).unwrap();
+        assert_ne!(adjusted, low_contrast.foreground.clone().unwrap());
+        assert!(adjusted.r > 0.3);
+        assert!(contrast_ratio(&adjusted, low_contrast.background.as_ref().unwrap()) >= 4.5);
+
+        assert_eq!(
+            high_contrast.legible_foreground(4.5),
+            high_contrast.foreground.clone()
+        );
+        assert_eq!(Colors::new(None, None, None).legible_foreground(4.5), None);
+    }
+
+    #[test]
+    fn test_decorations() {
+        let mut style = Style::new(COLORS);
+        assert!(style.decorations().is_empty());
+
+        // RUN FUNCTION
+        style.undercurl = true;
+        style.strikethrough = true;
+        assert_eq!(
+            style.decorations(),
+            DecorationSet::new(UnderlineStyle::Curl, true)
+        );
+        assert!(!style.decorations().is_empty());
+    }
 }
@@ -1,4 +1,4 @@
-mod animation_utils;
+pub mod animation_utils;
 mod blink;
 mod cursor_vfx;
 
@@ -15,6 +15,7 @@ use blink::*;
 
 const COMMAND_LINE_DELAY_FRAMES: u64 = 5;
 const DEFAULT_CELL_PERCENTAGE: f32 = 1.0 / 8.0;
+const TRAIL_STEPS: usize = 8;
 
 const STANDARD_CORNERS: &[(f32, f32); 4] = &[(-0.5, -0.5), (0.5, -0.5), (0.5, 0.5), (-0.5, 0.5)];
 
@@ -26,6 +27,7 @@ pub struct CursorSettings {
     animation_length: f32,
     animate_in_insert_mode: bool,
     trail_size: f32,
+    trail_length: f32,
     vfx_mode: cursor_vfx::VfxMode,
     vfx_opacity: f32,
     vfx_particle_lifetime: f32,
@@ -41,6 +43,7 @@ pub fn initialize_settings() {
         animation_length: 0.13,
         animate_in_insert_mode: true,
         trail_size: 0.7,
+        trail_length: 0.0,
         vfx_mode: cursor_vfx::VfxMode::Disabled,
         vfx_opacity: 200.0,
         vfx_particle_lifetime: 1.2,
@@ -57,6 +60,7 @@ pub fn initialize_settings() {
     );
     register_nvim_setting!("cursor_animation_length", CursorSettings::animation_length);
     register_nvim_setting!("cursor_trail_size", CursorSettings::trail_size);
+    register_nvim_setting!("cursor_trail_length", CursorSettings::trail_length);
     register_nvim_setting!("cursor_vfx_mode", CursorSettings::vfx_mode);
     register_nvim_setting!("cursor_vfx_opacity", CursorSettings::vfx_opacity);
     register_nvim_setting!(
@@ -234,6 +238,57 @@ impl CursorRenderer {
             .collect::<Vec<Corner>>();
     }
 
+    // Evenly spaced points from the animated cursor center towards its destination, covering
+    // trail_size of the remaining distance. A trail_size of 0 disables the trail
+    pub fn trail_points(&self, trail_size: f32, steps: usize) -> Vec<Point> {
+        if trail_size <= 0.0 || steps == 0 {
+            return Vec::new();
+        }
+
+        let center = self
+            .corners
+            .iter()
+            .fold(Point::new(0.0, 0.0), |sum, corner| {
+                sum + corner.current_position
+            })
+            * (1.0 / self.corners.len() as f32);
+        let destination = self.corners[0].previous_destination;
+        let end = ease_point(ease_linear, center, destination, trail_size.min(1.0));
+
+        (0..steps)
+            .map(|step| {
+                let t = if steps == 1 {
+                    0.0
+                } else {
+                    step as f32 / (steps - 1) as f32
+                };
+                ease_point(ease_linear, center, end, t)
+            })
+            .collect()
+    }
+
+    // Moves every corner towards the destination, or straight onto it for immediate movement
+    fn update_corners(
+        &mut self,
+        settings: &CursorSettings,
+        font_dimensions: Point,
+        destination: Point,
+        dt: f32,
+        immediate_movement: bool,
+    ) -> bool {
+        let mut animating = false;
+        for corner in self.corners.iter_mut() {
+            animating |= corner.update(
+                settings,
+                font_dimensions,
+                destination,
+                dt,
+                immediate_movement,
+            );
+        }
+        animating
+    }
+
     pub fn draw(
         &mut self,
         cursor: Cursor,
@@ -255,12 +310,14 @@ impl CursorRenderer {
         let mut paint = Paint::new(skulpin::skia_safe::colors::WHITE, None);
         paint.set_anti_alias(settings.antialiasing);
 
+        let snap_cursor = EDITOR.lock().take_cursor_snap();
+
         self.previous_position = {
             let editor = EDITOR.lock();
             let (_, grid_y) = cursor.position;
             let (_, previous_y) = self.previous_position;
 
-            if grid_y == editor.grid.height - 1 && previous_y != grid_y {
+            if grid_y == editor.grid.height - 1 && previous_y != grid_y && !snap_cursor {
                 self.command_line_delay += 1;
 
                 if self.command_line_delay < COMMAND_LINE_DELAY_FRAMES {
@@ -320,17 +377,13 @@ impl CursorRenderer {
         let mut animating = false;
 
         if !center_destination.is_zero() {
-            for corner in self.corners.iter_mut() {
-                let corner_animating = corner.update(
-                    &settings,
-                    font_dimensions,
-                    center_destination,
-                    dt,
-                    !settings.animate_in_insert_mode && in_insert_mode,
-                );
-
-                animating |= corner_animating;
-            }
+            animating |= self.update_corners(
+                &settings,
+                font_dimensions,
+                center_destination,
+                dt,
+                snap_cursor || (!settings.animate_in_insert_mode && in_insert_mode),
+            );
 
             let vfx_animating = if let Some(vfx) = self.cursor_vfx.as_mut() {
                 vfx.update(&settings, center_destination, (font_width, font_height), dt)
@@ -346,6 +399,16 @@ impl CursorRenderer {
         }
 
         if cursor.enabled && render {
+            // Draw the trail under the cursor, fading out towards the destination
+            if animating {
+                paint.set_color(cursor.background(&default_colors).to_color());
+                let trail = self.trail_points(settings.trail_length, TRAIL_STEPS);
+                for (step, point) in trail.iter().enumerate() {
+                    paint.set_alpha_f(1.0 - step as f32 / TRAIL_STEPS as f32);
+                    canvas.draw_circle(*point, font_height * 0.25, &paint);
+                }
+            }
+
             // Draw Background
             paint.set_color(cursor.background(&default_colors).to_color());
 
@@ -387,3 +450,76 @@ impl CursorRenderer {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_trail_points() {
+        let mut cursor_renderer = CursorRenderer::new();
+        for corner in cursor_renderer.corners.iter_mut() {
+            corner.current_position = Point::new(0.0, 0.0);
+            corner.previous_destination = Point::new(10.0, 20.0);
+        }
+
+        // RUN FUNCTION
+        let points = cursor_renderer.trail_points(1.0, 5);
+
+        assert_eq!(points.len(), 5);
+        for point in points.iter() {
+            assert!(point.x >= 0.0 && point.x <= 10.0);
+            assert!((point.y - point.x * 2.0).abs() < 0.001);
+        }
+        assert_eq!(points[0], Point::new(0.0, 0.0));
+        assert_eq!(points[4], Point::new(10.0, 20.0));
+    }
+
+    #[test]
+    fn test_trail_points_disabled() {
+        let cursor_renderer = CursorRenderer::new();
+
+        assert!(cursor_renderer.trail_points(0.0, 5).is_empty());
+    }
+
+    #[test]
+    fn test_corner_immediate_movement() {
+        initialize_settings();
+        let settings = SETTINGS.get::<CursorSettings>();
+        let mut corner = Corner::new();
+        let destination = Point::new(1000.0, 2000.0);
+
+        // RUN FUNCTION
+        corner.update(&settings, Point::new(10.0, 20.0), destination, 0.016, true);
+
+        assert_eq!(corner.current_position, destination);
+    }
+
+    #[test]
+    fn test_update_corners_snaps_mid_animation() {
+        initialize_settings();
+        let settings = SETTINGS.get::<CursorSettings>();
+        let mut cursor_renderer = CursorRenderer::new();
+        let font_dimensions = Point::new(10.0, 20.0);
+        cursor_renderer.update_corners(
+            &settings,
+            font_dimensions,
+            Point::new(50.0, 50.0),
+            0.016,
+            false,
+        );
+        let destination = Point::new(1000.0, 2000.0);
+        cursor_renderer.update_corners(&settings, font_dimensions, destination, 0.016, false);
+
+        // RUN FUNCTION
+        cursor_renderer.update_corners(&settings, font_dimensions, destination, 0.016, true);
+
+        for corner in cursor_renderer.corners.iter() {
+            let offset = Point::new(
+                corner.relative_position.x * font_dimensions.x,
+                corner.relative_position.y * font_dimensions.y,
+            );
+            assert_eq!(corner.current_position, destination + offset);
+        }
+    }
+}
@@ -1,8 +1,10 @@
 use std::sync::Arc;
 
-use log::trace;
+use log::{error, trace};
 use skulpin::skia_safe::gpu::SurfaceOrigin;
-use skulpin::skia_safe::{colors, dash_path_effect, Budgeted, Canvas, Paint, Rect, Surface};
+use skulpin::skia_safe::{
+    colors, dash_path_effect, Budgeted, Canvas, Color4f, Paint, Rect, Surface,
+};
 use skulpin::CoordinateSystemHelper;
 
 mod caching_shaper;
@@ -12,9 +14,49 @@ pub mod font_options;
 pub use caching_shaper::CachingShaper;
 pub use font_options::*;
 
-use crate::editor::{Style, EDITOR};
+use crate::editor::{EasingKind, Style, UnderlineStyle, EDITOR, MAIN_GRID};
+use crate::settings::*;
 use cursor_renderer::CursorRenderer;
 
+#[derive(Clone)]
+struct RendererSettings {
+    scroll_easing: EasingKind,
+}
+
+pub fn initialize_settings() {
+    SETTINGS.set(&RendererSettings {
+        scroll_easing: EasingKind::OutCubic,
+    });
+
+    register_nvim_setting!("scroll_easing", RendererSettings::scroll_easing);
+}
+
+impl FromValue for EasingKind {
+    fn from_value(&mut self, value: Value) {
+        if value.is_str() {
+            *self = match value.as_str().unwrap() {
+                "linear" => EasingKind::Linear,
+                "outcubic" => EasingKind::OutCubic,
+                value => {
+                    error!("Expected an easing name, but received {:?}", value);
+                    return;
+                }
+            };
+        } else {
+            error!("Expected an easing string, but received {:?}", value);
+        }
+    }
+}
+
+impl From<EasingKind> for Value {
+    fn from(kind: EasingKind) -> Self {
+        match kind {
+            EasingKind::Linear => Value::from("linear"),
+            EasingKind::OutCubic => Value::from("outcubic"),
+        }
+    }
+}
+
 pub struct Renderer {
     surface: Option<Surface>,
     paint: Paint,
@@ -103,27 +145,33 @@ impl Renderer {
 
         canvas.clip_rect(region, None, Some(false));
 
-        if style.underline || style.undercurl {
+        let decorations = style.decorations();
+        let underline_style = decorations.underline;
+        if underline_style != UnderlineStyle::None {
             let line_position = self.shaper.underline_position();
             let stroke_width = self.shaper.options.size / 10.0;
             self.paint
                 .set_color(style.special(&default_style.colors).to_color());
             self.paint.set_stroke_width(stroke_width);
 
-            if style.undercurl {
-                self.paint.set_path_effect(dash_path_effect::new(
-                    &[stroke_width * 2.0, stroke_width * 2.0],
-                    0.0,
-                ));
-            } else {
-                self.paint.set_path_effect(None);
-            }
-
-            canvas.draw_line(
-                (x, y - line_position + self.font_height),
-                (x + width, y - line_position + self.font_height),
-                &self.paint,
+            let intervals = match underline_style {
+                UnderlineStyle::Curl => Some([stroke_width * 2.0, stroke_width * 2.0]),
+                UnderlineStyle::Dotted => Some([stroke_width, stroke_width]),
+                UnderlineStyle::Dashed => Some([stroke_width * 4.0, stroke_width * 2.0]),
+                _ => None,
+            };
+            self.paint.set_path_effect(
+                intervals.and_then(|intervals| dash_path_effect::new(&intervals, 0.0)),
             );
+
+            let underline_y = y - line_position + self.font_height;
+            canvas.draw_line((x, underline_y), (x + width, underline_y), &self.paint);
+
+            // The second line goes above the first so it stays inside the cell
+            if underline_style == UnderlineStyle::Double {
+                let second_y = underline_y - stroke_width * 2.0;
+                canvas.draw_line((x, second_y), (x + width, second_y), &self.paint);
+            }
         }
 
         self.paint
@@ -139,7 +187,7 @@ impl Renderer {
             }
         }
 
-        if style.strikethrough {
+        if decorations.strikethrough {
             let line_position = region.center_y();
             self.paint
                 .set_color(style.special(&default_style.colors).to_color());
@@ -157,21 +205,53 @@ impl Renderer {
     ) -> bool {
         trace!("Rendering");
 
-        let ((draw_commands, should_clear), default_style, cursor, guifont_setting) = {
+        let (
+            debug_overlay_rects,
+            (mut draw_commands, should_clear),
+            background_changed,
+            grid_opacity,
+            color_filter,
+            color_levels,
+            mut default_style,
+            mut cursor,
+            guifont_setting,
+        ) = {
             let mut editor = EDITOR.lock();
+            editor.set_scroll_easing(SETTINGS.get::<RendererSettings>().scroll_easing);
             (
+                editor.debug_overlay_rects(),
                 editor.build_draw_commands(),
+                editor.take_background_changed(),
+                editor.grid_opacity(MAIN_GRID),
+                editor.color_filter.clone(),
+                editor.color_levels,
                 editor.default_style.clone(),
                 editor.cursor.clone(),
                 editor.guifont.clone(),
             )
         };
 
+        if !color_filter.is_identity() || color_levels.is_some() {
+            let filter_style = |style: &Arc<Style>| {
+                let mut filtered_style = style.as_ref().clone();
+                filtered_style.colors = style.colors.apply_filter(&color_filter);
+                if let Some(levels) = color_levels {
+                    filtered_style.colors = filtered_style.colors.quantize(levels);
+                }
+                Arc::new(filtered_style)
+            };
+            default_style = filter_style(&default_style);
+            cursor.style = cursor.style.as_ref().map(filter_style);
+            for command in draw_commands.iter_mut() {
+                command.style = command.style.as_ref().map(filter_style);
+            }
+        }
+
         let font_changed = guifont_setting
             .map(|guifont| self.update_font(&guifont))
             .unwrap_or(false);
 
-        if should_clear {
+        if should_clear || background_changed {
             self.surface = None;
         }
 
@@ -228,7 +308,24 @@ impl Renderer {
             window_size.height as f32,
         );
 
+        self.paint.set_alpha_f(grid_opacity);
         gpu_canvas.draw_image_rect(image, None, &image_destination, &self.paint);
+        self.paint.set_alpha_f(1.0);
+
+        // Tint the cells redrawn this frame
+        self.paint
+            .set_color(Color4f::new(1.0, 0.0, 0.0, 0.3).to_color());
+        for (x, y, width, height) in debug_overlay_rects {
+            let left = x as f32 * self.font_width;
+            let top = y as f32 * self.font_height;
+            let region = Rect::new(
+                left,
+                top,
+                left + width as f32 * self.font_width,
+                top + height as f32 * self.font_height,
+            );
+            gpu_canvas.draw_rect(region, &self.paint);
+        }
 
         self.surface = Some(surface);
         self.cursor_renderer.draw(
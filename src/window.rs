@@ -24,6 +24,8 @@ use crate::INITIAL_DIMENSIONS;
 #[folder = "assets/"]
 struct Asset;
 
+const PASTE_CHUNK_LINES: usize = 100;
+
 #[cfg(target_os = "windows")]
 fn windows_fix_dpi() {
     use winapi::shared::windef::DPI_AWARENESS_CONTEXT_PER_MONITOR_AWARE_V2;
@@ -35,12 +37,13 @@ fn windows_fix_dpi() {
 
 fn handle_new_grid_size(new_size: LogicalSize, renderer: &Renderer) {
     if new_size.width > 0 && new_size.height > 0 {
-        let new_width = ((new_size.width + 1) as f32 / renderer.font_width) as u32;
-        let new_height = ((new_size.height + 1) as f32 / renderer.font_height) as u32;
-        // Add 1 here to make sure resizing doesn't change the grid size on startup
+        let (new_width, new_height) = EDITOR.lock().grid_size_for_window(
+            (new_size.width, new_size.height),
+            (renderer.font_width, renderer.font_height),
+        );
         BRIDGE.queue_command(UiCommand::Resize {
-            width: new_width,
-            height: new_height,
+            width: new_width as u32,
+            height: new_height as u32,
         });
     }
 }
@@ -56,6 +59,7 @@ struct WindowWrapper {
     previous_size: LogicalSize,
     transparency: f32,
     fullscreen: bool,
+    suspended: bool,
     cached_size: (u32, u32),
     cached_position: (i32, i32),
 }
@@ -171,6 +175,7 @@ impl WindowWrapper {
             previous_size: logical_size,
             transparency: 1.0,
             fullscreen: false,
+            suspended: false,
             cached_size: (0, 0),
             cached_position: (0, 0),
         }
@@ -247,6 +252,15 @@ impl WindowWrapper {
             }
         }
 
+        let suspended = { EDITOR.lock().suspended };
+
+        if self.suspended != suspended {
+            self.suspended = suspended;
+            if suspended {
+                self.window.minimize();
+            }
+        }
+
         let fullscreen = { SETTINGS.get::<WindowSettings>().fullscreen };
 
         if self.fullscreen != fullscreen {
@@ -270,12 +284,37 @@ impl WindowWrapper {
             );
         }
 
+        if keycode == Some(Keycode::Paste) {
+            self.handle_paste();
+            return;
+        }
+
         if let Some(keybinding_string) = produce_neovim_keybinding_string(keycode, text, modifiers)
         {
             BRIDGE.queue_command(UiCommand::Keyboard(keybinding_string));
         }
     }
 
+    // Streams the clipboard to Neovim a few lines at a time
+    pub fn handle_paste(&mut self) {
+        let clipboard_text = self
+            .context
+            .video()
+            .and_then(|video| video.clipboard().clipboard_text());
+
+        match clipboard_text {
+            Ok(text) => {
+                let lines: Vec<&str> = text.split_inclusive('\n').collect();
+                let chunks = lines.chunks(PASTE_CHUNK_LINES).map(|chunk| chunk.concat());
+                let pastes = EDITOR.lock().paste_stream(chunks);
+                for (data, phase) in pastes {
+                    BRIDGE.queue_command(UiCommand::Paste { data, phase });
+                }
+            }
+            Err(error) => error!("Could not read the clipboard: {}", error),
+        }
+    }
+
     pub fn handle_pointer_motion(&mut self, x: i32, y: i32) {
         let previous_position = self.mouse_position;
         let physical_size = PhysicalSize::new(
@@ -290,13 +329,30 @@ impl WindowWrapper {
                 self.mouse_position.width,
                 self.mouse_position.height,
             ));
+        } else if !self.mouse_down
+            && EDITOR.lock().mouse_move(
+                self.mouse_position.height as u64,
+                self.mouse_position.width as u64,
+            )
+        {
+            BRIDGE.queue_command(UiCommand::MouseMove(
+                self.mouse_position.width,
+                self.mouse_position.height,
+            ));
         }
     }
 
-    pub fn handle_pointer_down(&mut self) {
+    pub fn handle_pointer_down(&mut self, timestamp: u32) {
+        let click_count = EDITOR.lock().mouse_press_timed(
+            self.mouse_position.height as u64,
+            self.mouse_position.width as u64,
+            "left",
+            timestamp as u64,
+        );
         BRIDGE.queue_command(UiCommand::MouseButton {
             action: String::from("press"),
             position: (self.mouse_position.width, self.mouse_position.height),
+            click_count,
         });
         self.mouse_down = true;
     }
@@ -305,6 +361,7 @@ impl WindowWrapper {
         BRIDGE.queue_command(UiCommand::MouseButton {
             action: String::from("release"),
             position: (self.mouse_position.width, self.mouse_position.height),
+            click_count: 1,
         });
         self.mouse_down = false;
     }
@@ -342,7 +399,13 @@ impl WindowWrapper {
     }
 
     pub fn handle_focus_gained(&mut self) {
+        {
+            let mut editor = EDITOR.lock();
+            editor.set_suspended(false);
+            editor.snap_cursor();
+        }
         BRIDGE.queue_command(UiCommand::FocusGained);
+        BRIDGE.queue_command(UiCommand::RefreshMode);
         REDRAW_SCHEDULER.queue_next_frame();
     }
 
@@ -358,6 +421,10 @@ impl WindowWrapper {
             self.previous_size = new_size;
         }
 
+        if self.suspended {
+            return true;
+        }
+
         debug!("Render Triggered");
 
         let current_size = self.previous_size;
@@ -442,7 +509,7 @@ pub fn ui_loop() {
                 }
                 Event::TextInput { text, .. } => keytext = Some(text),
                 Event::MouseMotion { x, y, .. } => window.handle_pointer_motion(x, y),
-                Event::MouseButtonDown { .. } => window.handle_pointer_down(),
+                Event::MouseButtonDown { timestamp, .. } => window.handle_pointer_down(timestamp),
                 Event::MouseButtonUp { .. } => window.handle_pointer_up(),
                 Event::MouseWheel { x, y, .. } => window.handle_mouse_wheel(x, y),
                 Event::Window {